@@ -1,4 +1,4 @@
-use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 use mrecordlog::MultiRecordLog;
 
 fn bench_single_size(size: usize, count: usize, loop_count: usize) {
@@ -44,5 +44,95 @@ fn insert_throughput(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, insert_throughput);
+fn read_throughput(c: &mut Criterion) {
+    let record_sizes: [usize; 2] = [1 << 8, 1 << 14];
+    let bytes_written: usize = 1 << 22;
+
+    let mut group = c.benchmark_group("read speed");
+    group.throughput(criterion::Throughput::Bytes(bytes_written as _));
+
+    for record_size in record_sizes {
+        let tempdir = tempfile::tempdir().unwrap();
+        let mut record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        record_log.create_queue("q1").unwrap();
+        let record = vec![0; record_size];
+        record_log
+            .append_records(
+                "q1",
+                None,
+                std::iter::repeat(&record[..]).take(bytes_written / record_size),
+            )
+            .unwrap();
+
+        group.bench_with_input(
+            BenchmarkId::new("bench_range_throughput", format!("size={record_size}")),
+            &record_log,
+            |b, record_log| {
+                b.iter(|| {
+                    record_log
+                        .range("q1", ..)
+                        .unwrap()
+                        // Reads every byte, so that the benchmark does not only measure
+                        // getting a view on the payloads.
+                        .map(|record| {
+                            record
+                                .payload
+                                .iter()
+                                .fold(0u64, |sum, byte| sum.wrapping_add(u64::from(*byte)))
+                        })
+                        .fold(0u64, |sum, payload_sum| {
+                            black_box(sum.wrapping_add(payload_sum))
+                        })
+                });
+            },
+        );
+    }
+}
+
+fn bench_append_truncate(size: usize, loop_count: usize) {
+    let tempdir = tempfile::tempdir().unwrap();
+    let mut record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+    record_log.create_queue("q1").unwrap();
+
+    let record = vec![0; size];
+
+    for _ in 0..loop_count {
+        let append_outcome = record_log
+            .append_records("q1", None, std::iter::repeat(&record[..]).take(16))
+            .unwrap();
+        // Keeps the 64 last records, like a consumer lagging behind.
+        if let Some(truncate_up_to) = append_outcome.last_position.unwrap().checked_sub(64) {
+            record_log.truncate("q1", ..=truncate_up_to).unwrap();
+        }
+    }
+}
+
+fn append_truncate_throughput(c: &mut Criterion) {
+    let record_sizes: [usize; 2] = [1 << 8, 1 << 14];
+    let bytes_written: usize = 1 << 22;
+
+    let mut group = c.benchmark_group("append and truncate speed");
+    group.throughput(criterion::Throughput::Bytes(bytes_written as _));
+
+    for record_size in record_sizes {
+        let loop_count = bytes_written / 16 / record_size;
+        group.bench_with_input(
+            BenchmarkId::new(
+                "bench_append_truncate_throughput",
+                format!("size={record_size}"),
+            ),
+            &(record_size, loop_count),
+            |b, (record_size, loop_count)| {
+                b.iter(|| bench_append_truncate(*record_size, *loop_count));
+            },
+        );
+    }
+}
+
+criterion_group!(
+    benches,
+    insert_throughput,
+    read_throughput,
+    append_truncate_throughput
+);
 criterion_main!(benches);
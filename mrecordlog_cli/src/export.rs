@@ -0,0 +1,92 @@
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use serde::{Deserialize, Serialize};
+
+/// Format of the files written by `export` and read by `import`.
+#[derive(Debug, Clone, Copy)]
+pub enum FileFormat {
+    /// One JSON object per line, with the position and the base64 encoded payload.
+    JsonLines,
+    /// For every record, the position as a little endian u64, the length of the payload as a
+    /// little endian u32, and the payload.
+    Binary,
+}
+
+impl FromStr for FileFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> anyhow::Result<FileFormat> {
+        match format {
+            "jsonl" => Ok(FileFormat::JsonLines),
+            "binary" => Ok(FileFormat::Binary),
+            _ => anyhow::bail!("unknown file format `{format}`, expected `jsonl` or `binary`"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonRecord {
+    position: u64,
+    payload: String,
+}
+
+pub fn write_record(
+    wrt: &mut impl Write,
+    file_format: FileFormat,
+    position: u64,
+    payload: &[u8],
+) -> anyhow::Result<()> {
+    match file_format {
+        FileFormat::JsonLines => {
+            let json_record = JsonRecord {
+                position,
+                payload: BASE64_STANDARD.encode(payload),
+            };
+            serde_json::to_writer(&mut *wrt, &json_record)?;
+            wrt.write_all(b"\n")?;
+        }
+        FileFormat::Binary => {
+            wrt.write_all(&position.to_le_bytes())?;
+            wrt.write_all(&u32::try_from(payload.len())?.to_le_bytes())?;
+            wrt.write_all(payload)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads every record of the file, calling `record_fn` with its position and payload.
+pub fn read_records(
+    mut reader: impl BufRead,
+    file_format: FileFormat,
+    mut record_fn: impl FnMut(u64, Vec<u8>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    match file_format {
+        FileFormat::JsonLines => {
+            for line_res in reader.lines() {
+                let line = line_res?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let json_record: JsonRecord = serde_json::from_str(&line)?;
+                let payload = BASE64_STANDARD.decode(json_record.payload)?;
+                record_fn(json_record.position, payload)?;
+            }
+        }
+        FileFormat::Binary => loop {
+            let mut position_bytes = [0u8; 8];
+            match reader.read_exact(&mut position_bytes) {
+                Ok(()) => {}
+                Err(io_err) if io_err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(io_err) => return Err(io_err.into()),
+            }
+            let mut len_bytes = [0u8; 4];
+            reader.read_exact(&mut len_bytes)?;
+            let mut payload = vec![0u8; u32::from_le_bytes(len_bytes) as usize];
+            reader.read_exact(&mut payload)?;
+            record_fn(u64::from_le_bytes(position_bytes), payload)?;
+        },
+    }
+    Ok(())
+}
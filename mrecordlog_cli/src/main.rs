@@ -1,8 +1,32 @@
+mod export;
+mod output;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::ops::Bound;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use mrecordlog::MultiRecordLog;
+use anyhow::Context;
+use export::FileFormat;
+use mrecordlog::inspect::{
+    file_stats, list_wal_files, wal_filepath, FileStats, FrameStatus, ReplayedWal, WalEntry,
+    WalFollower, WalIssue, WalRecord, WalScanner,
+};
+use mrecordlog::{MultiRecordLog, PersistAction, PersistPolicy, QueuesSummary};
+use output::{OutputFormat, PayloadEncoding, PrintedRecord};
+use serde::Serialize;
 use structopt::StructOpt;
 
+#[derive(Debug, StructOpt)]
+struct Opt {
+    /// Format of the output: `table`, `json` or `debug`.
+    #[structopt(long, global = true, default_value = "table")]
+    format: OutputFormat,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
 #[derive(Debug, StructOpt)]
 enum Command {
     Summary {
@@ -13,42 +37,850 @@ enum Command {
         #[structopt(short = "f", default_value = "./wal")]
         wal_path: PathBuf,
         queue_name: String,
+        /// Keeps printing the records appended by the process writing the wal, until the queue
+        /// gets deleted. The wal is only read.
+        #[structopt(long)]
+        follow: bool,
+        /// Encoding of the payloads: `utf8`, `hex`, `base64` or `raw`. Raw payloads are written
+        /// as is, one after the other.
+        #[structopt(long, default_value = "utf8")]
+        payload_encoding: PayloadEncoding,
+        /// Position of the first record to print.
+        #[structopt(long)]
+        from: Option<u64>,
+        /// Position of the last record to print.
+        #[structopt(long)]
+        to: Option<u64>,
+    },
+    /// Prints every frame of the wal files, and the records they carry.
+    Dump {
+        #[structopt(short = "f", default_value = "./wal")]
+        wal_path: PathBuf,
+    },
+    /// Checks the wal files without modifying them, and exits with a non-zero status if they
+    /// hold corrupted data.
+    Verify {
+        #[structopt(short = "f", default_value = "./wal")]
+        wal_path: PathBuf,
+    },
+    /// Shows how the space of each wal file is used: live and truncated payload bytes, padding,
+    /// and the queues preventing the file from being garbage collected. The wal is not modified.
+    Stats {
+        #[structopt(short = "f", default_value = "./wal")]
+        wal_path: PathBuf,
+    },
+    /// Writes the live state of the queues that can be recovered from a wal to a new, empty,
+    /// directory. The source directory is not modified.
+    Repair {
+        #[structopt(long = "from")]
+        from_path: PathBuf,
+        #[structopt(long = "to")]
+        to_path: PathBuf,
+    },
+    /// Writes the records of a queue to a file, in the `jsonl` or `binary` format. The wal is
+    /// not modified.
+    Export {
+        #[structopt(short = "f", default_value = "./wal")]
+        wal_path: PathBuf,
+        queue_name: String,
+        /// Path of the file to write, `-` for stdout.
+        #[structopt(short = "o", long = "output", default_value = "-")]
+        output_path: PathBuf,
+        #[structopt(long = "file-format", default_value = "jsonl")]
+        file_format: FileFormat,
+    },
+    /// Appends the records of a file written by `export` to a queue, at the same positions.
+    /// The queue is created if it does not exist. Records at positions the queue already holds
+    /// are skipped.
+    Import {
+        #[structopt(short = "f", default_value = "./wal")]
+        wal_path: PathBuf,
+        queue_name: String,
+        /// Path of the file to read, `-` for stdin.
+        #[structopt(short = "i", long = "input", default_value = "-")]
+        input_path: PathBuf,
+        #[structopt(long = "file-format", default_value = "jsonl")]
+        file_format: FileFormat,
+    },
+    CreateQueue {
+        #[structopt(short = "f", default_value = "./wal")]
+        wal_path: PathBuf,
+        queue_name: String,
+    },
+    /// Deletes a queue and all of its records.
+    DeleteQueue {
+        #[structopt(short = "f", default_value = "./wal")]
+        wal_path: PathBuf,
+        queue_name: String,
+        /// Confirms the deletion.
+        #[structopt(long)]
+        yes: bool,
+    },
+    /// Removes the records of a queue up to a position, included.
+    Truncate {
+        #[structopt(short = "f", default_value = "./wal")]
+        wal_path: PathBuf,
+        #[structopt(long = "queue")]
+        queue_name: String,
+        #[structopt(long = "up-to")]
+        up_to: u64,
+        /// Confirms the truncation.
+        #[structopt(long)]
+        yes: bool,
+    },
+    /// Appends a record read from stdin to a queue.
+    Append {
+        #[structopt(short = "f", default_value = "./wal")]
+        wal_path: PathBuf,
+        #[structopt(long = "queue")]
+        queue_name: String,
+        /// Position of the record. Defaults to the next position of the queue.
+        #[structopt(long)]
+        position: Option<u64>,
     },
 }
 
-fn run_summary(path: &Path) -> anyhow::Result<()> {
+fn run_summary(path: &Path, format: OutputFormat) -> anyhow::Result<()> {
     let multi_record_log = MultiRecordLog::open(path)?;
     let summary = multi_record_log.summary();
-    for (queue, summary) in summary.queues {
-        println!("{}", queue);
-        println!("{summary:?}");
-    }
+    println!("{}", format.render(&summary, summary_table)?);
     Ok(())
 }
 
-fn run_read_queue(path: &Path, queue_name: &str) -> anyhow::Result<()> {
-    let multi_record_log = MultiRecordLog::open(path)?;
-    for record in multi_record_log.range(queue_name, ..)? {
-        let Ok(payload_str) = std::str::from_utf8(&record.payload) else {
-            eprintln!("Payload is not utf8: {:?}", record.payload);
-            continue;
+fn summary_table(summary: &QueuesSummary) -> String {
+    let global = &summary.global;
+    let mut table = format!(
+        "{} files, current file {}, persist policy {:?}\n",
+        global.num_files, global.current_file_number, global.persist_policy
+    );
+    let queue_width = summary
+        .queues
+        .keys()
+        .map(String::len)
+        .max()
+        .unwrap_or(0)
+        .max("queue".len());
+    table.push_str(&format!(
+        "{:<queue_width$} {:>10} {:>10} {:>10} {:>14} {:>14} {:>6} {:>12}",
+        "queue", "start", "end", "records", "payload bytes", "memory bytes", "files", "durability"
+    ));
+    for (queue, queue_summary) in &summary.queues {
+        let end = queue_summary
+            .end
+            .map(|end| end.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        table.push_str(&format!(
+            "\n{queue:<queue_width$} {:>10} {end:>10} {:>10} {:>14} {:>14} {:>6} {:>12}",
+            queue_summary.start,
+            queue_summary.num_records,
+            queue_summary.payload_bytes,
+            queue_summary.memory_capacity_bytes,
+            queue_summary.num_files,
+            format!("{:?}", queue_summary.durability_class),
+        ));
+    }
+    table
+}
+
+/// Prints the records of a queue within a range of positions.
+struct RecordPrinter {
+    format: OutputFormat,
+    payload_encoding: PayloadEncoding,
+    from: Option<u64>,
+    to: Option<u64>,
+}
+
+impl RecordPrinter {
+    fn new(
+        format: OutputFormat,
+        payload_encoding: PayloadEncoding,
+        from: Option<u64>,
+        to: Option<u64>,
+    ) -> anyhow::Result<RecordPrinter> {
+        if matches!(payload_encoding, PayloadEncoding::Raw)
+            && !matches!(format, OutputFormat::Table)
+        {
+            anyhow::bail!("the `raw` payload encoding is only available with the `table` format");
+        }
+        if let (Some(from), Some(to)) = (from, to) {
+            if from > to {
+                anyhow::bail!("--from must not be greater than --to");
+            }
+        }
+        Ok(RecordPrinter {
+            format,
+            payload_encoding,
+            from,
+            to,
+        })
+    }
+
+    fn range(&self) -> (Bound<u64>, Bound<u64>) {
+        let start = self.from.map(Bound::Included).unwrap_or(Bound::Unbounded);
+        let end = self.to.map(Bound::Included).unwrap_or(Bound::Unbounded);
+        (start, end)
+    }
+
+    fn print(&self, position: u64, payload: &[u8]) -> anyhow::Result<()> {
+        if self.from.map(|from| position < from).unwrap_or(false)
+            || self.to.map(|to| position > to).unwrap_or(false)
+        {
+            return Ok(());
+        }
+        if let PayloadEncoding::Raw = self.payload_encoding {
+            std::io::stdout().write_all(payload)?;
+            return Ok(());
+        }
+        let Some(payload) = self.payload_encoding.encode(payload) else {
+            eprintln!(
+                "Payload at position {position} is not utf8, use --payload-encoding to print it"
+            );
+            return Ok(());
         };
-        println!("{payload_str}");
+        let printed_record = PrintedRecord { position, payload };
+        println!(
+            "{}",
+            self.format.render(&printed_record, |record| format!(
+                "{} {}",
+                record.position, record.payload
+            ))?
+        );
+        Ok(())
+    }
+}
+
+fn run_read_queue(
+    path: &Path,
+    queue_name: &str,
+    record_printer: &RecordPrinter,
+) -> anyhow::Result<()> {
+    let multi_record_log = MultiRecordLog::open(path)?;
+    for record in multi_record_log.range(queue_name, record_printer.range())? {
+        record_printer.print(record.position, &record.payload)?;
+    }
+    Ok(())
+}
+
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+fn run_follow_queue(
+    path: &Path,
+    queue_name: &str,
+    record_printer: &RecordPrinter,
+) -> anyhow::Result<()> {
+    let mut follower = WalFollower::open(path)?;
+    // The queue is rebuilt from what was written so far, so that records already truncated
+    // are not printed.
+    let mut replayed_wal = ReplayedWal::default();
+    let mut is_caught_up = false;
+    // The queue may have been deleted, and created again, earlier in the wal.
+    let mut is_deleted_while_catching_up = false;
+    loop {
+        let entries = follower.poll()?;
+        for entry in entries {
+            let record_info = match entry {
+                WalEntry::Record(record_info) => record_info,
+                WalEntry::Issue(issue) => {
+                    eprintln!("{}", record_printer.format.render(&issue, issue_line)?);
+                    continue;
+                }
+                WalEntry::Frame(_) => continue,
+            };
+            let is_deleted = matches!(
+                &record_info.record,
+                WalRecord::DeleteQueue { queue, .. } if queue == queue_name
+            );
+            let next_position_opt = replayed_wal
+                .queues
+                .get(queue_name)
+                .map(|queue| queue.next_position());
+            replayed_wal.apply(record_info.record, record_info.location.file_number);
+            if !is_caught_up {
+                is_deleted_while_catching_up |= is_deleted;
+                continue;
+            }
+            if is_deleted {
+                eprintln!("Queue `{queue_name}` was deleted");
+                return Ok(());
+            }
+            let Some(queue) = replayed_wal.queues.get(queue_name) else {
+                continue;
+            };
+            let next_position = next_position_opt.unwrap_or(0);
+            let first_new_record = queue
+                .records
+                .partition_point(|record| record.position < next_position);
+            for record in queue.records.range(first_new_record..) {
+                record_printer.print(record.position, &record.payload)?;
+            }
+        }
+        if !is_caught_up {
+            is_caught_up = true;
+            if let Some(queue) = replayed_wal.queues.get(queue_name) {
+                for record in &queue.records {
+                    record_printer.print(record.position, &record.payload)?;
+                }
+            } else if is_deleted_while_catching_up {
+                eprintln!("Queue `{queue_name}` was deleted");
+                return Ok(());
+            }
+        }
+        std::io::stdout().flush()?;
+        std::thread::sleep(FOLLOW_POLL_INTERVAL);
+    }
+}
+
+fn issue_line(issue: &WalIssue) -> String {
+    format!("{}: {}", issue.location, issue.kind)
+}
+
+fn run_dump(path: &Path, format: OutputFormat) -> anyhow::Result<()> {
+    for entry_res in WalScanner::open(path)? {
+        println!("{}", format.render(&entry_res?, dump_line)?);
+    }
+    Ok(())
+}
+
+fn dump_line(entry: &WalEntry) -> String {
+    match entry {
+        WalEntry::Frame(frame_info) => {
+            let frame_type = frame_info
+                .frame_type
+                .map(|frame_type| format!("{frame_type:?}"))
+                .unwrap_or_else(|| "?".to_string());
+            let crc = match frame_info.status {
+                FrameStatus::Valid => "ok",
+                FrameStatus::BadChecksum => "bad",
+                FrameStatus::CorruptedHeader => "corrupted header",
+                FrameStatus::SpansBlocks => "spans blocks",
+            };
+            format!(
+                "{} frame={frame_type} len={} crc={crc}",
+                frame_info.location, frame_info.len
+            )
+        }
+        WalEntry::Record(record_info) => {
+            let record_id = record_info
+                .record_id
+                .map(|(epoch, sequence)| format!("{epoch}:{sequence}"))
+                .unwrap_or_else(|| "-".to_string());
+            format!(
+                "    record id={record_id} len={} {}",
+                record_info.len, record_info.record
+            )
+        }
+        WalEntry::Issue(issue) => format!("    issue: {}", issue.kind),
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+struct VerifyReport {
+    num_files: usize,
+    num_frames: usize,
+    num_records: usize,
+    issues: Vec<WalIssue>,
+}
+
+/// Returns true if no issue was found.
+fn run_verify(path: &Path, format: OutputFormat) -> anyhow::Result<bool> {
+    let mut report = VerifyReport {
+        num_files: list_wal_files(path)?.len(),
+        ..Default::default()
+    };
+    for entry_res in WalScanner::open(path)? {
+        match entry_res? {
+            WalEntry::Frame(_) => report.num_frames += 1,
+            WalEntry::Record(_) => report.num_records += 1,
+            WalEntry::Issue(issue) => report.issues.push(issue),
+        }
+    }
+    println!(
+        "{}",
+        format.render(&report, |report| {
+            let mut table = String::new();
+            for issue in &report.issues {
+                table.push_str(&issue_line(issue));
+                table.push('\n');
+            }
+            table.push_str(&format!(
+                "{} files, {} frames, {} records, {} issues",
+                report.num_files,
+                report.num_frames,
+                report.num_records,
+                report.issues.len()
+            ));
+            table
+        })?
+    );
+    Ok(report.issues.is_empty())
+}
+
+fn run_stats(path: &Path, format: OutputFormat) -> anyhow::Result<()> {
+    let stats = file_stats(path)?;
+    println!("{}", format.render(&stats, stats_table)?);
+    Ok(())
+}
+
+fn stats_table(stats: &Vec<FileStats>) -> String {
+    let mut table = format!(
+        "{:<24} {:>12} {:>12} {:>12} {:>10}  pinned by",
+        "file", "written", "live", "truncated", "padding"
+    );
+    let mut total = FileStats::default();
+    let mut total_live_bytes = 0;
+    for file_stats in stats {
+        let file_name = wal_filepath(Path::new(""), file_stats.file_number);
+        let pinned_by: Vec<&str> = file_stats.pinned_by().collect();
+        table.push_str(&format!(
+            "\n{:<24} {:>12} {:>12} {:>12} {:>10}  {}",
+            file_name.display(),
+            file_stats.written_bytes,
+            file_stats.total_live_bytes(),
+            file_stats.truncated_bytes,
+            file_stats.padding_bytes,
+            pinned_by.join(", ")
+        ));
+        total.written_bytes += file_stats.written_bytes;
+        total.truncated_bytes += file_stats.truncated_bytes;
+        total.padding_bytes += file_stats.padding_bytes;
+        total_live_bytes += file_stats.total_live_bytes();
+    }
+    table.push_str(&format!(
+        "\n{:<24} {:>12} {:>12} {:>12} {:>10}",
+        "total", total.written_bytes, total_live_bytes, total.truncated_bytes, total.padding_bytes
+    ));
+    table
+}
+
+#[derive(Debug, Serialize)]
+struct RepairedQueue {
+    queue: String,
+    num_records: usize,
+    next_position: u64,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct RepairReport {
+    /// Issues of the source wal. The data they affect was dropped.
+    dropped_issues: Vec<WalIssue>,
+    /// Records dropped because their position was already taken, as `(queue, position)`.
+    rejected_records: Vec<(String, u64)>,
+    queues: Vec<RepairedQueue>,
+    num_live_records: usize,
+    num_appended_records: usize,
+}
+
+fn run_repair(from_path: &Path, to_path: &Path, format: OutputFormat) -> anyhow::Result<()> {
+    std::fs::create_dir_all(to_path)?;
+    if from_path.canonicalize()? == to_path.canonicalize()? {
+        anyhow::bail!("the source and destination directories must be different");
+    }
+    if std::fs::read_dir(to_path)?.next().is_some() {
+        anyhow::bail!(
+            "the destination directory {} is not empty",
+            to_path.display()
+        );
+    }
+    let replayed_wal = ReplayedWal::replay(from_path)?;
+    let mut report = RepairReport {
+        num_appended_records: replayed_wal.num_appended_records,
+        dropped_issues: replayed_wal.issues.clone(),
+        rejected_records: replayed_wal.rejected_records.clone(),
+        ..Default::default()
+    };
+
+    let mut multi_record_log = MultiRecordLog::open_with_prefs(to_path, PersistPolicy::DoNothing)?;
+    for (queue, replayed_queue) in &replayed_wal.queues {
+        multi_record_log.create_queue_with_class(queue, replayed_queue.durability_class)?;
+        let start_position = replayed_queue
+            .records
+            .front()
+            .map(|record| record.position)
+            .unwrap_or_else(|| replayed_queue.next_position());
+        if start_position > 0 {
+            // Truncating past the end of a queue moves it forward.
+            multi_record_log.truncate(queue, ..=start_position - 1)?;
+        }
+        for record in &replayed_queue.records {
+            multi_record_log.append_record(queue, Some(record.position), &record.payload[..])?;
+        }
+        report.num_live_records += replayed_queue.records.len();
+        report.queues.push(RepairedQueue {
+            queue: queue.clone(),
+            num_records: replayed_queue.records.len(),
+            next_position: replayed_queue.next_position(),
+        });
+    }
+    multi_record_log.persist(PersistAction::FlushAndFsync)?;
+    println!("{}", format.render(&report, repair_table)?);
+    Ok(())
+}
+
+fn repair_table(report: &RepairReport) -> String {
+    let mut table = String::new();
+    for issue in &report.dropped_issues {
+        table.push_str(&format!("dropped {}\n", issue_line(issue)));
+    }
+    for (queue, position) in &report.rejected_records {
+        table.push_str(&format!(
+            "dropped record {queue}:{position}: position already taken\n"
+        ));
+    }
+    for repaired_queue in &report.queues {
+        table.push_str(&format!(
+            "{}: {} records, next position {}\n",
+            repaired_queue.queue, repaired_queue.num_records, repaired_queue.next_position
+        ));
+    }
+    table.push_str(&format!(
+        "{} queues, {} live records out of {} appended, {} issues, {} rejected records",
+        report.queues.len(),
+        report.num_live_records,
+        report.num_appended_records,
+        report.dropped_issues.len(),
+        report.rejected_records.len()
+    ));
+    table
+}
+
+#[derive(Debug, Serialize)]
+struct TransferReport {
+    queue: String,
+    num_records: usize,
+}
+
+fn run_export(
+    path: &Path,
+    queue_name: &str,
+    output_path: &Path,
+    file_format: FileFormat,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let replayed_wal = ReplayedWal::replay(path)?;
+    let Some(replayed_queue) = replayed_wal.queues.get(queue_name) else {
+        anyhow::bail!("queue `{queue_name}` does not exist");
+    };
+    let mut wrt: BufWriter<Box<dyn Write>> = if output_path == Path::new("-") {
+        BufWriter::new(Box::new(std::io::stdout().lock()))
+    } else {
+        BufWriter::new(Box::new(File::create(output_path)?))
+    };
+    for record in &replayed_queue.records {
+        export::write_record(&mut wrt, file_format, record.position, &record.payload)?;
     }
+    wrt.flush()?;
+    let report = TransferReport {
+        queue: queue_name.to_string(),
+        num_records: replayed_queue.records.len(),
+    };
+    // The records may be written to stdout.
+    eprintln!(
+        "{}",
+        format.render(&report, |report| format!(
+            "exported {} records",
+            report.num_records
+        ))?
+    );
+    Ok(())
+}
+
+/// Maximum size of the payloads appended together by `import`.
+const IMPORT_BATCH_NUM_BYTES: usize = 1_000_000;
+
+/// Records with contiguous positions, appended to a queue together.
+#[derive(Default)]
+struct ImportBatch {
+    start_position: u64,
+    payloads: Vec<Vec<u8>>,
+    num_bytes: usize,
+}
+
+impl ImportBatch {
+    /// Appends the records of the batch to the queue, and returns how many there were.
+    fn append(
+        &mut self,
+        multi_record_log: &mut MultiRecordLog,
+        queue_name: &str,
+    ) -> anyhow::Result<usize> {
+        if self.payloads.is_empty() {
+            return Ok(0);
+        }
+        let num_records = self.payloads.len();
+        let positions = self.start_position..self.start_position + num_records as u64;
+        multi_record_log
+            .append_records(
+                queue_name,
+                Some(self.start_position),
+                self.payloads.iter().map(|payload| &payload[..]),
+            )
+            .with_context(|| format!("failed to append the records at positions {positions:?}"))?;
+        self.payloads.clear();
+        self.num_bytes = 0;
+        Ok(num_records)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ImportReport {
+    queue: String,
+    num_records: usize,
+    num_skipped_records: usize,
+}
+
+fn run_import(
+    path: &Path,
+    queue_name: &str,
+    input_path: &Path,
+    file_format: FileFormat,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let mut multi_record_log = MultiRecordLog::open_with_prefs(path, PersistPolicy::DoNothing)?;
+    if !multi_record_log.queue_exists(queue_name) {
+        multi_record_log.create_queue(queue_name)?;
+    }
+    let queue_summary = &multi_record_log.summary().queues[queue_name];
+    // Records the queue already holds are skipped, so that an interrupted import can be run
+    // again.
+    let next_position = queue_summary
+        .end
+        .map(|end| end + 1)
+        .unwrap_or(queue_summary.start);
+    let reader: Box<dyn std::io::BufRead> = if input_path == Path::new("-") {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(File::open(input_path)?))
+    };
+    let mut batch = ImportBatch::default();
+    let mut num_records = 0;
+    let mut num_skipped_records = 0;
+    let import_res = export::read_records(reader, file_format, |position, payload| {
+        if position < next_position {
+            num_skipped_records += 1;
+            return Ok(());
+        }
+        if !batch.payloads.is_empty()
+            && (position != batch.start_position + batch.payloads.len() as u64
+                || batch.num_bytes >= IMPORT_BATCH_NUM_BYTES)
+        {
+            num_records += batch.append(&mut multi_record_log, queue_name)?;
+        }
+        if batch.payloads.is_empty() {
+            batch.start_position = position;
+        }
+        batch.num_bytes += payload.len();
+        batch.payloads.push(payload);
+        Ok(())
+    })
+    .and_then(|()| {
+        num_records += batch.append(&mut multi_record_log, queue_name)?;
+        Ok(())
+    });
+    // The records appended before an error are persisted too.
+    let persist_res = multi_record_log.persist(PersistAction::FlushAndFsync);
+    import_res?;
+    persist_res?;
+    let report = ImportReport {
+        queue: queue_name.to_string(),
+        num_records,
+        num_skipped_records,
+    };
+    eprintln!(
+        "{}",
+        format.render(&report, |report| format!(
+            "imported {} records, skipped {} records already in the queue",
+            report.num_records, report.num_skipped_records
+        ))?
+    );
+    Ok(())
+}
+
+fn ensure_confirmed(yes: bool, action: &str) -> anyhow::Result<()> {
+    if !yes {
+        anyhow::bail!("{action} cannot be undone, pass --yes to confirm");
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct QueueReport {
+    queue: String,
+}
+
+fn run_create_queue(path: &Path, queue_name: &str, format: OutputFormat) -> anyhow::Result<()> {
+    let mut multi_record_log = MultiRecordLog::open(path)?;
+    multi_record_log.create_queue(queue_name)?;
+    let report = QueueReport {
+        queue: queue_name.to_string(),
+    };
+    println!(
+        "{}",
+        format.render(&report, |report| format!("created queue {}", report.queue))?
+    );
+    Ok(())
+}
+
+fn run_delete_queue(
+    path: &Path,
+    queue_name: &str,
+    yes: bool,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    ensure_confirmed(yes, "deleting a queue")?;
+    let mut multi_record_log = MultiRecordLog::open(path)?;
+    multi_record_log.delete_queue(queue_name)?;
+    let report = QueueReport {
+        queue: queue_name.to_string(),
+    };
+    println!(
+        "{}",
+        format.render(&report, |report| format!("deleted queue {}", report.queue))?
+    );
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct TruncateReport {
+    queue: String,
+    up_to: u64,
+    evicted_records: usize,
+}
+
+fn run_truncate(
+    path: &Path,
+    queue_name: &str,
+    up_to: u64,
+    yes: bool,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    ensure_confirmed(yes, "truncating a queue")?;
+    let mut multi_record_log = MultiRecordLog::open(path)?;
+    let truncate_outcome = multi_record_log.truncate(queue_name, ..=up_to)?;
+    multi_record_log.persist(PersistAction::FlushAndFsync)?;
+    let report = TruncateReport {
+        queue: queue_name.to_string(),
+        up_to,
+        evicted_records: truncate_outcome.evicted_records,
+    };
+    println!(
+        "{}",
+        format.render(&report, |report| format!(
+            "truncated queue {} up to {}, {} records removed",
+            report.queue, report.up_to, report.evicted_records
+        ))?
+    );
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct AppendReport {
+    queue: String,
+    /// `None` if the record at that position was already appended.
+    position: Option<u64>,
+}
+
+fn run_append(
+    path: &Path,
+    queue_name: &str,
+    position: Option<u64>,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let mut payload = Vec::new();
+    std::io::stdin().lock().read_to_end(&mut payload)?;
+    let mut multi_record_log = MultiRecordLog::open(path)?;
+    let append_outcome = multi_record_log.append_record(queue_name, position, &payload[..])?;
+    multi_record_log.persist(PersistAction::FlushAndFsync)?;
+    let report = AppendReport {
+        queue: queue_name.to_string(),
+        position: append_outcome.last_position,
+    };
+    println!(
+        "{}",
+        format.render(&report, |report| match report.position {
+            Some(position) => format!("appended record at position {position}"),
+            None => "the record at that position was already appended".to_string(),
+        })?
+    );
     Ok(())
 }
 
 fn main() -> anyhow::Result<()> {
-    let command = Command::from_args();
+    let Opt { format, command } = Opt::from_args();
     match command {
         Command::Summary { wal_path } => {
-            run_summary(&wal_path)?;
+            run_summary(&wal_path, format)?;
         }
         Command::Read {
             queue_name,
             wal_path,
+            follow,
+            payload_encoding,
+            from,
+            to,
+        } => {
+            let record_printer = RecordPrinter::new(format, payload_encoding, from, to)?;
+            if follow {
+                run_follow_queue(&wal_path, &queue_name, &record_printer)?;
+            } else {
+                run_read_queue(&wal_path, &queue_name, &record_printer)?;
+            }
+        }
+        Command::Dump { wal_path } => {
+            run_dump(&wal_path, format)?;
+        }
+        Command::Stats { wal_path } => {
+            run_stats(&wal_path, format)?;
+        }
+        Command::Repair { from_path, to_path } => {
+            run_repair(&from_path, &to_path, format)?;
+        }
+        Command::Export {
+            wal_path,
+            queue_name,
+            output_path,
+            file_format,
+        } => {
+            run_export(&wal_path, &queue_name, &output_path, file_format, format)?;
+        }
+        Command::Import {
+            wal_path,
+            queue_name,
+            input_path,
+            file_format,
+        } => {
+            run_import(&wal_path, &queue_name, &input_path, file_format, format)?;
+        }
+        Command::CreateQueue {
+            wal_path,
+            queue_name,
+        } => {
+            run_create_queue(&wal_path, &queue_name, format)?;
+        }
+        Command::DeleteQueue {
+            wal_path,
+            queue_name,
+            yes,
+        } => {
+            run_delete_queue(&wal_path, &queue_name, yes, format)?;
+        }
+        Command::Truncate {
+            wal_path,
+            queue_name,
+            up_to,
+            yes,
         } => {
-            run_read_queue(&wal_path, &queue_name)?;
+            run_truncate(&wal_path, &queue_name, up_to, yes, format)?;
+        }
+        Command::Append {
+            wal_path,
+            queue_name,
+            position,
+        } => {
+            run_append(&wal_path, &queue_name, position, format)?;
+        }
+        Command::Verify { wal_path } => {
+            if !run_verify(&wal_path, format)? {
+                std::process::exit(1);
+            }
         }
     }
     Ok(())
@@ -0,0 +1,92 @@
+use std::fmt;
+use std::str::FromStr;
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use serde::Serialize;
+
+/// How the results of a command are printed.
+#[derive(Debug, Clone, Copy)]
+pub enum OutputFormat {
+    /// One JSON document per result. Commands printing a stream of results print one per line.
+    Json,
+    /// Human readable output.
+    Table,
+    /// The `Debug` representation of the results.
+    Debug,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> anyhow::Result<OutputFormat> {
+        match format {
+            "json" => Ok(OutputFormat::Json),
+            "table" => Ok(OutputFormat::Table),
+            "debug" => Ok(OutputFormat::Debug),
+            _ => anyhow::bail!("unknown format `{format}`, expected `json`, `table` or `debug`"),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Renders a result, calling `table_fn` for the human readable output.
+    pub fn render<T: Serialize + fmt::Debug>(
+        self,
+        value: &T,
+        table_fn: impl FnOnce(&T) -> String,
+    ) -> anyhow::Result<String> {
+        match self {
+            OutputFormat::Json => Ok(serde_json::to_string(value)?),
+            OutputFormat::Table => Ok(table_fn(value)),
+            OutputFormat::Debug => Ok(format!("{value:?}")),
+        }
+    }
+}
+
+/// How payloads are printed.
+#[derive(Debug, Clone, Copy)]
+pub enum PayloadEncoding {
+    Utf8,
+    Hex,
+    Base64,
+    /// The payload bytes, as is. Only available with the `table` format.
+    Raw,
+}
+
+impl FromStr for PayloadEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(encoding: &str) -> anyhow::Result<PayloadEncoding> {
+        match encoding {
+            "utf8" => Ok(PayloadEncoding::Utf8),
+            "hex" => Ok(PayloadEncoding::Hex),
+            "base64" => Ok(PayloadEncoding::Base64),
+            "raw" => Ok(PayloadEncoding::Raw),
+            _ => anyhow::bail!(
+                "unknown payload encoding `{encoding}`, expected `utf8`, `hex`, `base64` or `raw`"
+            ),
+        }
+    }
+}
+
+impl PayloadEncoding {
+    /// Encodes a payload as a string. Returns `None` if the payload is not valid utf8 while the
+    /// `utf8` encoding was asked for.
+    ///
+    /// Raw payloads are not strings, and are written directly by the caller.
+    pub fn encode(self, payload: &[u8]) -> Option<String> {
+        match self {
+            PayloadEncoding::Utf8 => std::str::from_utf8(payload).ok().map(str::to_string),
+            PayloadEncoding::Hex => Some(hex::encode(payload)),
+            PayloadEncoding::Base64 => Some(BASE64_STANDARD.encode(payload)),
+            PayloadEncoding::Raw => None,
+        }
+    }
+}
+
+/// A record of a queue, with its payload encoded.
+#[derive(Debug, Serialize)]
+pub struct PrintedRecord {
+    pub position: u64,
+    pub payload: String,
+}
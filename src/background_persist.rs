@@ -0,0 +1,103 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+use crate::durability::DurabilityWatcher;
+use crate::error::is_disk_full;
+use crate::recordlog::RecordWriter;
+use crate::rolling::RollingWriter;
+use crate::PersistAction;
+
+/// WAL writer shared between the log and its background persister.
+pub(crate) type SharedWriter = Arc<Mutex<RecordWriter<RollingWriter>>>;
+
+pub(crate) fn lock_writer(writer: &SharedWriter) -> MutexGuard<'_, RecordWriter<RollingWriter>> {
+    // The writer is only poisoned if the background persister panicked while persisting. The
+    // writer itself is left in a state the next write or persist call will report on.
+    writer
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Persists the shared writer. Once an fsync completed, every position written so far is
+/// durable.
+pub(crate) fn persist_and_track_durability(
+    writer: &SharedWriter,
+    durability_watcher: &DurabilityWatcher,
+    persist_action: PersistAction,
+) -> io::Result<()> {
+    let mut writer_guard = lock_writer(writer);
+    writer_guard.persist(persist_action)?;
+    if persist_action.is_fsync() {
+        // The writer is still locked: no record was written since the fsync.
+        durability_watcher.fsync_completed();
+    }
+    Ok(())
+}
+
+/// Thread persisting the WAL at a fixed interval, so that data written right before traffic
+/// stops does not stay buffered indefinitely.
+///
+/// The thread stops when this handle is dropped.
+///
+/// When persisting fails because the disk is full, `disk_full` is set so that the log switches
+/// to degraded mode.
+pub(crate) struct BackgroundPersister {
+    stop_tx: Option<mpsc::Sender<()>>,
+    join_handle: Option<JoinHandle<()>>,
+}
+
+impl BackgroundPersister {
+    pub fn spawn(
+        writer: SharedWriter,
+        durability_watcher: DurabilityWatcher,
+        interval: Duration,
+        persist_action: PersistAction,
+        disk_full: Arc<AtomicBool>,
+    ) -> io::Result<BackgroundPersister> {
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let join_handle = std::thread::Builder::new()
+            .name("mrecordlog-persist".to_string())
+            .spawn(move || loop {
+                match stop_rx.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => {
+                        debug!("stopping background persister");
+                        return;
+                    }
+                }
+                if !lock_writer(&writer).needs_persist(persist_action) {
+                    continue;
+                }
+                // Errors are not lost: whatever could not be persisted is still buffered, and
+                // the next write or persist will fail the same way.
+                if let Err(io_err) =
+                    persist_and_track_durability(&writer, &durability_watcher, persist_action)
+                {
+                    warn!(error=?io_err, "background persist failed");
+                    if is_disk_full(&io_err) {
+                        disk_full.store(true, Ordering::Relaxed);
+                    }
+                }
+            })?;
+        Ok(BackgroundPersister {
+            stop_tx: Some(stop_tx),
+            join_handle: Some(join_handle),
+        })
+    }
+}
+
+impl Drop for BackgroundPersister {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the thread up.
+        self.stop_tx.take();
+        if let Some(join_handle) = self.join_handle.take() {
+            let _ = join_handle.join();
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+#[derive(Default)]
+struct Watermarks {
+    // Last position written to the WAL, per queue, not covered by an fsync yet.
+    pending: HashMap<String, u64>,
+    // Last position covered by a completed fsync, per queue.
+    durable: HashMap<String, u64>,
+}
+
+#[derive(Default)]
+struct Inner {
+    watermarks: Mutex<Watermarks>,
+    durable_updated: Condvar,
+}
+
+/// Tracks, for every queue, the last position that reached the disk.
+///
+/// A position is durable once a `FlushAndFsync` persist completed after it was appended, be it
+/// requested explicitly, by the persist policy, or by the background persister. Records
+/// replayed when opening the log are durable.
+///
+/// The watcher is cheap to clone and can be moved to other threads, to wait for appended
+/// records to become durable while the log keeps being written.
+#[derive(Clone, Default)]
+pub struct DurabilityWatcher {
+    inner: Arc<Inner>,
+}
+
+impl DurabilityWatcher {
+    fn lock(&self) -> MutexGuard<'_, Watermarks> {
+        self.inner
+            .watermarks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns the last durable position of the queue, or `None` if none of its records is
+    /// durable (or the queue does not exist).
+    pub fn durable_position(&self, queue: &str) -> Option<u64> {
+        self.lock().durable.get(queue).copied()
+    }
+
+    /// Waits until `position` is durable for the queue, or the timeout is elapsed.
+    ///
+    /// Returns true if the position is durable.
+    pub fn wait_for(&self, queue: &str, position: u64, timeout: Duration) -> bool {
+        let watermarks = self.lock();
+        let (watermarks, _) = self
+            .inner
+            .durable_updated
+            .wait_timeout_while(watermarks, timeout, |watermarks| {
+                !is_durable(watermarks, queue, position)
+            })
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        is_durable(&watermarks, queue, position)
+    }
+
+    /// Records that positions up to `last_position` were written to the WAL for the queue.
+    pub(crate) fn record_written(&self, queue: &str, last_position: u64) {
+        let mut watermarks = self.lock();
+        if let Some(pending_position) = watermarks.pending.get_mut(queue) {
+            *pending_position = (*pending_position).max(last_position);
+        } else {
+            watermarks.pending.insert(queue.to_string(), last_position);
+        }
+    }
+
+    /// Makes every position written so far durable.
+    ///
+    /// Must be called after the fsync completed, while still holding the lock on the WAL writer
+    /// so that no record gets written in between.
+    pub(crate) fn fsync_completed(&self) {
+        let mut watermarks = self.lock();
+        if watermarks.pending.is_empty() {
+            return;
+        }
+        let Watermarks { pending, durable } = &mut *watermarks;
+        for (queue, position) in pending.drain() {
+            let durable_position = durable.entry(queue).or_insert(position);
+            *durable_position = (*durable_position).max(position);
+        }
+        self.inner.durable_updated.notify_all();
+    }
+
+    /// Marks positions up to `last_position` as durable, for records replayed from disk.
+    pub(crate) fn set_durable(&self, queue: &str, last_position: u64) {
+        self.lock().durable.insert(queue.to_string(), last_position);
+    }
+
+    pub(crate) fn remove_queue(&self, queue: &str) {
+        let mut watermarks = self.lock();
+        watermarks.pending.remove(queue);
+        watermarks.durable.remove(queue);
+    }
+}
+
+fn is_durable(watermarks: &Watermarks, queue: &str, position: u64) -> bool {
+    watermarks
+        .durable
+        .get(queue)
+        .map(|durable_position| *durable_position >= position)
+        .unwrap_or(false)
+}
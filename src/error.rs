@@ -5,12 +5,38 @@ use thiserror::Error;
 #[derive(Debug, Copy, Clone)]
 pub struct AlreadyExists;
 
+/// Returns true if the error was caused by the disk (or the user quota) being full.
+pub(crate) fn is_disk_full(io_err: &io::Error) -> bool {
+    #[cfg(unix)]
+    const DISK_FULL_ERRORS: &[i32] = &[28]; // ENOSPC
+    #[cfg(windows)]
+    const DISK_FULL_ERRORS: &[i32] = &[39, 112]; // ERROR_HANDLE_DISK_FULL, ERROR_DISK_FULL
+    #[cfg(not(any(unix, windows)))]
+    const DISK_FULL_ERRORS: &[i32] = &[];
+    io_err
+        .raw_os_error()
+        .map(|code| DISK_FULL_ERRORS.contains(&code))
+        .unwrap_or(false)
+}
+
 #[derive(Error, Debug)]
 pub enum CreateQueueError {
     #[error("Already exists")]
     AlreadyExists,
+    #[error("Disk full: the record log is read-only")]
+    DiskFull,
     #[error("Io error: {0}")]
-    IoError(#[from] io::Error),
+    IoError(io::Error),
+}
+
+impl From<io::Error> for CreateQueueError {
+    fn from(io_err: io::Error) -> Self {
+        if is_disk_full(&io_err) {
+            CreateQueueError::DiskFull
+        } else {
+            CreateQueueError::IoError(io_err)
+        }
+    }
 }
 
 impl From<AlreadyExists> for CreateQueueError {
@@ -23,8 +49,20 @@ impl From<AlreadyExists> for CreateQueueError {
 pub enum DeleteQueueError {
     #[error("Missing queue")]
     MissingQueue(String),
+    #[error("Disk full: the record log is read-only")]
+    DiskFull,
     #[error("Io error: {0}")]
-    IoError(#[from] io::Error),
+    IoError(io::Error),
+}
+
+impl From<io::Error> for DeleteQueueError {
+    fn from(io_err: io::Error) -> Self {
+        if is_disk_full(&io_err) {
+            DeleteQueueError::DiskFull
+        } else {
+            DeleteQueueError::IoError(io_err)
+        }
+    }
 }
 
 impl From<MissingQueue> for DeleteQueueError {
@@ -60,13 +98,30 @@ impl From<MissingQueue> for TruncateError {
 #[derive(Error, Debug)]
 pub enum AppendError {
     #[error("Io error: {0}")]
-    IoError(#[from] io::Error),
+    IoError(io::Error),
+    /// The disk is full and the record log is read-only until space is freed.
+    ///
+    /// If the records could be fully written to the WAL buffer before the error, they are
+    /// appended to the in-memory queue and will reach the disk once the log recovers: retrying
+    /// with the same `position_opt` is a no-op in that case.
+    #[error("Disk full: the record log is read-only")]
+    DiskFull,
     #[error("Missing queue: {0}")]
     MissingQueue(String),
     #[error("Past")]
     Past,
 }
 
+impl From<io::Error> for AppendError {
+    fn from(io_err: io::Error) -> Self {
+        if is_disk_full(&io_err) {
+            AppendError::DiskFull
+        } else {
+            AppendError::IoError(io_err)
+        }
+    }
+}
+
 impl From<MissingQueue> for AppendError {
     fn from(missing_queue: MissingQueue) -> Self {
         AppendError::MissingQueue(missing_queue.0)
@@ -91,3 +146,34 @@ pub enum ReadRecordError {
     #[error("Corruption")]
     Corruption,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{is_disk_full, AppendError};
+
+    #[test]
+    fn test_is_disk_full() {
+        #[cfg(unix)]
+        assert!(is_disk_full(&io::Error::from_raw_os_error(28)));
+        assert!(!is_disk_full(&io::Error::from(io::ErrorKind::NotFound)));
+        assert!(!is_disk_full(&io::Error::new(
+            io::ErrorKind::Other,
+            "other"
+        )));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_disk_full_append_error() {
+        assert!(matches!(
+            AppendError::from(io::Error::from_raw_os_error(28)),
+            AppendError::DiskFull
+        ));
+        assert!(matches!(
+            AppendError::from(io::Error::from(io::ErrorKind::NotFound)),
+            AppendError::IoError(_)
+        ));
+    }
+}
@@ -1,3 +1,5 @@
+use serde::Serialize;
+
 pub const HEADER_LEN: usize = 4 + 2 + 1;
 
 fn crc32(data: &[u8], frame_type: u8) -> u32 {
@@ -64,8 +66,10 @@ impl Header {
     }
 }
 
+/// Place of a frame within its record. Records that do not fit in the remaining space of a
+/// block are split into a `First` frame, `Middle` frames, and a `Last` frame.
 #[repr(u8)]
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
 pub enum FrameType {
     Full = 1u8,
     First = 2u8,
@@ -2,9 +2,10 @@ mod header;
 mod reader;
 mod writer;
 
-use self::header::Header;
-pub(crate) use self::header::{FrameType, HEADER_LEN};
-pub use self::reader::{FrameReader, ReadFrameError};
+pub use self::header::FrameType;
+pub(crate) use self::header::{Header, HEADER_LEN};
+pub(crate) use self::reader::DecodedFrame;
+pub use self::reader::{FrameReader, FrameStatus, ReadFrameError};
 pub use self::writer::FrameWriter;
 
 #[cfg(test)]
@@ -1,5 +1,6 @@
 use std::io;
 
+use serde::Serialize;
 use thiserror::Error;
 
 use crate::frame::{FrameType, FrameWriter, Header, HEADER_LEN};
@@ -16,6 +17,30 @@ pub struct FrameReader<R> {
     block_corrupted: bool,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum FrameStatus {
+    Valid,
+    /// The header could not be decoded. The rest of the block is skipped.
+    CorruptedHeader,
+    /// The checksum of the payload does not match the header.
+    BadChecksum,
+    /// The header announces a payload going past the end of the block. The rest of the block
+    /// is skipped.
+    SpansBlocks,
+}
+
+/// A frame found by [`FrameReader::peek_frame`], valid or not.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct DecodedFrame {
+    /// Offset of the frame within its block.
+    pub offset: usize,
+    /// `None` if the header is corrupted.
+    pub frame_type: Option<FrameType>,
+    /// Length of the payload, excluding the header.
+    pub len: usize,
+    pub status: FrameStatus,
+}
+
 #[derive(Error, Debug)]
 pub enum ReadFrameError {
     #[error("Io error: {0}")]
@@ -39,6 +64,10 @@ impl<R: BlockRead + Unpin> FrameReader<R> {
         &self.reader
     }
 
+    pub(crate) fn read_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
     // Returns the number of bytes remaining into
     // the current block.
     //
@@ -62,16 +91,63 @@ impl<R: BlockRead + Unpin> FrameReader<R> {
         Ok(())
     }
 
-    // Attempt to read the header of the next frame
-    // This method does not consume any bytes (which is why it is called get and not read).
-    fn get_frame_header(&mut self) -> Result<Header, ReadFrameError> {
+    /// Decodes the next frame without consuming it.
+    ///
+    /// Unlike `read_frame`, corrupted frames are returned rather than reported as errors.
+    pub(crate) fn peek_frame(&mut self) -> Result<DecodedFrame, ReadFrameError> {
+        self.go_to_next_block_if_necessary()?;
         let header_bytes: &[u8] = &self.reader.block()[self.cursor..][..HEADER_LEN];
         if header_bytes == [0u8; HEADER_LEN] {
             return Err(ReadFrameError::NotAvailable);
         }
-        match Header::deserialize(header_bytes) {
-            Some(header) => Ok(header),
-            None => {
+        let Some(header) = Header::deserialize(header_bytes) else {
+            return Ok(DecodedFrame {
+                offset: self.cursor,
+                frame_type: None,
+                len: 0,
+                status: FrameStatus::CorruptedHeader,
+            });
+        };
+        let payload_start = self.cursor + HEADER_LEN;
+        let status = if payload_start + header.len() > BLOCK_NUM_BYTES {
+            FrameStatus::SpansBlocks
+        } else if !header.check(&self.reader.block()[payload_start..][..header.len()]) {
+            FrameStatus::BadChecksum
+        } else {
+            FrameStatus::Valid
+        };
+        Ok(DecodedFrame {
+            offset: self.cursor,
+            frame_type: Some(header.frame_type()),
+            len: header.len(),
+            status,
+        })
+    }
+
+    /// Consumes the frame returned by the last call to `peek_frame`.
+    pub(crate) fn consume_frame(
+        &mut self,
+        frame: &DecodedFrame,
+    ) -> Result<(FrameType, &[u8]), ReadFrameError> {
+        match (frame.status, frame.frame_type) {
+            (FrameStatus::Valid, Some(frame_type)) => {
+                let payload_start = self.cursor + HEADER_LEN;
+                self.cursor = payload_start + frame.len;
+                Ok((frame_type, &self.reader.block()[payload_start..self.cursor]))
+            }
+            (FrameStatus::BadChecksum, _) => {
+                // The CRC check is wrong.
+                // We do not necessarily need to corrupt the block.
+                //
+                // With a little luck, a single frame payload byte was corrupted
+                // but the frame length was correct.
+                self.cursor += HEADER_LEN + frame.len;
+                Err(ReadFrameError::Corruption)
+            }
+            _ => {
+                // Either the header is corrupted, or the number of bytes for this frame would
+                // span over the next block.
+                // This is a corruption for which we need to drop the entire block.
                 self.block_corrupted = true;
                 Err(ReadFrameError::Corruption)
             }
@@ -79,28 +155,22 @@ impl<R: BlockRead + Unpin> FrameReader<R> {
     }
 
     // Reads the next frame.
+    #[cfg(test)]
     pub fn read_frame(&mut self) -> Result<(FrameType, &[u8]), ReadFrameError> {
-        self.go_to_next_block_if_necessary()?;
-        let header = self.get_frame_header()?;
-        self.cursor += HEADER_LEN;
-        if self.cursor + header.len() > BLOCK_NUM_BYTES {
-            // The number of bytes for this frame would span over
-            // the next block.
-            // This is a corruption for which we need to drop the entire block.
-            self.block_corrupted = true;
-            return Err(ReadFrameError::Corruption);
-        }
-        let frame_payload = &self.reader.block()[self.cursor..][..header.len()];
-        self.cursor += header.len();
-        if !header.check(frame_payload) {
-            // The CRC check is wrong.
-            // We do not necessarily need to corrupt the block.
-            //
-            // With a little luck, a single frame payload byte was corrupted
-            // but the frame length was correct.
-            return Err(ReadFrameError::Corruption);
+        let frame = self.peek_frame()?;
+        self.consume_frame(&frame)
+    }
+
+    /// Skips the rest of the current block, even if more frames could be written in it.
+    ///
+    /// Returns false if there is no next block.
+    pub(crate) fn skip_to_next_block(&mut self) -> io::Result<bool> {
+        if !self.reader.next_block()? {
+            return Ok(false);
         }
-        Ok((header.frame_type(), frame_payload))
+        self.cursor = 0;
+        self.block_corrupted = false;
+        Ok(true)
     }
 }
 
@@ -108,6 +178,7 @@ impl FrameReader<RollingReader> {
     pub fn into_writer(self) -> io::Result<FrameWriter<RollingWriter>> {
         let mut rolling_writer: RollingWriter = self.reader.into_writer()?;
         rolling_writer.forward(self.cursor)?;
-        Ok(FrameWriter::create(rolling_writer))
+        let stats = rolling_writer.directory.stats.clone();
+        Ok(FrameWriter::create_with_stats(rolling_writer, stats))
     }
 }
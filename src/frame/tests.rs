@@ -114,3 +114,58 @@ fn test_multiple_blocks_corruption_on_length() -> io::Result<()> {
     ));
     Ok(())
 }
+
+#[test]
+fn test_pad_block_isolates_torn_frame() {
+    use crate::frame::header::Header;
+    use crate::BlockWrite;
+
+    let mut wrt = VecBlockWriter::default();
+    let mut torn_frame = [0u8; HEADER_LEN + 10];
+    Header::for_payload(FrameType::First, &[1u8; 100]).serialize(&mut torn_frame[..HEADER_LEN]);
+    wrt.write(&torn_frame).unwrap();
+    let mut frame_writer = FrameWriter::create(wrt);
+    let num_padding_bytes = frame_writer.pad_block().unwrap();
+    assert_eq!(num_padding_bytes, BLOCK_NUM_BYTES - HEADER_LEN - 10);
+    assert_eq!(frame_writer.pad_block().unwrap(), 0);
+    frame_writer
+        .write_frame(FrameType::Full, &b"hello"[..])
+        .unwrap();
+    let buffer: Vec<u8> = frame_writer.into_writer().into();
+    let mut frame_reader = FrameReader::open(ArrayReader::from(&buffer[..]));
+    loop {
+        match frame_reader.read_frame() {
+            Ok((frame_type, payload)) => {
+                assert_eq!(frame_type, FrameType::Full);
+                assert_eq!(payload, b"hello");
+                break;
+            }
+            Err(ReadFrameError::Corruption) => {}
+            Err(err) => panic!("unexpected error {err:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_pad_block_is_skipped_by_readers() {
+    let mut frame_writer = FrameWriter::create(VecBlockWriter::default());
+    frame_writer
+        .write_frame(FrameType::Full, &b"abc"[..])
+        .unwrap();
+    frame_writer.pad_block().unwrap();
+    frame_writer
+        .write_frame(FrameType::Full, &b"de"[..])
+        .unwrap();
+    let buffer: Vec<u8> = frame_writer.into_writer().into();
+    let mut frame_reader = FrameReader::open(ArrayReader::from(&buffer[..]));
+    assert_eq!(
+        frame_reader.read_frame().unwrap(),
+        (FrameType::Full, &b"abc"[..])
+    );
+    let (frame_type, _) = frame_reader.read_frame().unwrap();
+    assert_eq!(frame_type, FrameType::Middle);
+    assert_eq!(
+        frame_reader.read_frame().unwrap(),
+        (FrameType::Full, &b"de"[..])
+    );
+}
@@ -1,20 +1,32 @@
 use std::io;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use bytes::Buf;
 
 use crate::frame::{FrameType, Header, HEADER_LEN};
 use crate::rolling::{Directory, RollingWriter};
+use crate::stats::IoStatsRecorder;
 use crate::{BlockWrite, PersistAction, BLOCK_NUM_BYTES};
 
 pub struct FrameWriter<W> {
     wrt: W,
     // temporary buffer, not storing anything in particular after any function returns
     buffer: Box<[u8; BLOCK_NUM_BYTES]>,
+    stats: Arc<IoStatsRecorder>,
 }
 
 impl<W: BlockWrite + Unpin> FrameWriter<W> {
+    #[cfg(test)]
     pub fn create(wrt: W) -> Self {
+        Self::create_with_stats(wrt, Arc::default())
+    }
+
+    pub(crate) fn create_with_stats(wrt: W, stats: Arc<IoStatsRecorder>) -> Self {
         FrameWriter {
             wrt,
             buffer: Box::new([0u8; BLOCK_NUM_BYTES]),
+            stats,
         }
     }
 
@@ -23,7 +35,11 @@ impl<W: BlockWrite + Unpin> FrameWriter<W> {
     ///
     /// Returns the number of bytes pushed to the underlying
     /// writer (header + payload, plus any zero-padding written to close out the current block).
-    pub fn write_frame(&mut self, frame_type: FrameType, payload: &[u8]) -> io::Result<usize> {
+    pub fn write_frame(
+        &mut self,
+        frame_type: FrameType,
+        mut payload: impl Buf,
+    ) -> io::Result<usize> {
         let mut num_bytes_written = 0;
         let num_bytes_remaining_in_block = self.wrt.num_bytes_remaining_in_block();
 
@@ -31,18 +47,45 @@ impl<W: BlockWrite + Unpin> FrameWriter<W> {
             let zero_bytes = [0u8; HEADER_LEN];
             self.wrt
                 .write(&zero_bytes[..num_bytes_remaining_in_block])?;
+            self.stats.record_padding(num_bytes_remaining_in_block);
             num_bytes_written += num_bytes_remaining_in_block;
         }
-        let record_len = HEADER_LEN + payload.len();
+        let record_len = HEADER_LEN + payload.remaining();
         let (buffer_header, buffer_record) = self.buffer[..record_len].split_at_mut(HEADER_LEN);
-        buffer_record.copy_from_slice(payload);
-        Header::for_payload(frame_type, payload).serialize(buffer_header);
+        payload.copy_to_slice(buffer_record);
+        Header::for_payload(frame_type, buffer_record).serialize(buffer_header);
         self.wrt.write(&self.buffer[..record_len])?;
+        self.stats.record_frame();
 
         num_bytes_written += record_len;
         Ok(num_bytes_written)
     }
 
+    /// Fills the rest of the current block, so that the next frame starts on a fresh block.
+    ///
+    /// This is used to isolate a frame that may have been partially written because of an io
+    /// error. The padding is a `Middle` frame, which readers ignore when they are not within a
+    /// record. Its payload is made of `0xFF` bytes: a reader desynchronized by a torn frame will
+    /// read them as a corrupted header and skip to the next block, instead of considering it
+    /// the end of the log.
+    pub fn pad_block(&mut self) -> io::Result<usize> {
+        let num_bytes_remaining_in_block = self.wrt.num_bytes_remaining_in_block();
+        if num_bytes_remaining_in_block == BLOCK_NUM_BYTES {
+            return Ok(0);
+        }
+        if num_bytes_remaining_in_block < HEADER_LEN {
+            let padding_bytes = [0xFFu8; HEADER_LEN];
+            self.wrt
+                .write(&padding_bytes[..num_bytes_remaining_in_block])?;
+            self.stats.record_padding(num_bytes_remaining_in_block);
+            return Ok(num_bytes_remaining_in_block);
+        }
+        let padding_payload = vec![0xFFu8; num_bytes_remaining_in_block - HEADER_LEN];
+        let num_bytes_written = self.write_frame(FrameType::Middle, &padding_payload[..])?;
+        self.stats.record_padding(num_bytes_written);
+        Ok(num_bytes_written)
+    }
+
     /// Flush the buffered writer used in the FrameWriter.
     ///
     /// When writing to a file, this performs a syscall and
@@ -77,4 +120,21 @@ impl FrameWriter<RollingWriter> {
     pub fn directory(&mut self) -> &mut Directory {
         &mut self.wrt.directory
     }
+
+    pub fn index_records(&mut self, queue: &str, positions: RangeInclusive<u64>) {
+        self.wrt.index_records(queue, positions)
+    }
+
+    pub fn commit_indexed_records(&mut self, written: bool) {
+        self.wrt.commit_indexed_records(written)
+    }
+
+    pub fn resync_offset(&mut self) -> io::Result<()> {
+        self.wrt.resync_offset()
+    }
+
+    #[cfg(test)]
+    pub fn simulate_disk_full(&mut self, num_bytes_available: Option<usize>) {
+        self.wrt.simulate_disk_full(num_bytes_available)
+    }
 }
@@ -0,0 +1,219 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use super::scanner::{EntryCollector, WalBlockRead};
+use super::{list_wal_files, wal_filepath, WalEntry, WalLocation};
+use crate::frame::HEADER_LEN;
+use crate::recordlog::RecordReader;
+use crate::rolling::NUM_BLOCKS_PER_FILE;
+use crate::{BlockRead, BLOCK_NUM_BYTES};
+
+/// Reads the frames and records of a wal directory as another process writes them.
+///
+/// Like the [`WalScanner`](super::WalScanner), the follower only reads files. Each call to
+/// [`WalFollower::poll`] returns what was written since the previous call, picking up new
+/// blocks of the current file and newly rolled files. A frame that is only partially written
+/// is not reported, and is read again by the next poll.
+pub struct WalFollower {
+    record_reader: RecordReader<FollowedBlocks>,
+    collector: EntryCollector,
+}
+
+impl WalFollower {
+    /// Opens a follower starting at the beginning of the first wal file of the directory.
+    pub fn open(dir_path: &Path) -> io::Result<WalFollower> {
+        // Fails early if the directory cannot be read.
+        list_wal_files(dir_path)?;
+        let blocks = FollowedBlocks {
+            dir_path: dir_path.to_path_buf(),
+            file_opt: None,
+            block_id: 0,
+            block: Box::new([0u8; BLOCK_NUM_BYTES]),
+            is_last_written_block: true,
+        };
+        Ok(WalFollower {
+            record_reader: RecordReader::open(blocks),
+            collector: EntryCollector::default(),
+        })
+    }
+
+    /// Returns the entries written since the previous call.
+    pub fn poll(&mut self) -> io::Result<Vec<WalEntry>> {
+        // The writer may have written more of the current block since the previous call.
+        self.record_reader.read_mut().reload()?;
+        loop {
+            if self.collector.read_next(&mut self.record_reader)? {
+                continue;
+            }
+            // What remains of the last written block is read again by the next poll.
+            if self.record_reader.read().is_last_written_block
+                || !self.record_reader.skip_to_next_block()?
+            {
+                break;
+            }
+        }
+        let mut entries = Vec::new();
+        while let Some(entry) = self.collector.pop_entry() {
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+/// Blocks of the wal files of a directory, as they get written.
+///
+/// Until the first file is opened, the current block is an empty block preceding it.
+struct FollowedBlocks {
+    dir_path: PathBuf,
+    file_opt: Option<(u64, File)>,
+    block_id: usize,
+    block: Box<[u8; BLOCK_NUM_BYTES]>,
+    // The writer had not started writing the block following the current one when it was read.
+    is_last_written_block: bool,
+}
+
+impl FollowedBlocks {
+    fn current_file_number(&self) -> Option<u64> {
+        self.file_opt.as_ref().map(|(file_number, _)| *file_number)
+    }
+
+    /// Reads the current block again.
+    fn reload(&mut self) -> io::Result<()> {
+        let Some((file_number, file)) = &mut self.file_opt else {
+            self.block.fill(0);
+            self.is_last_written_block = !has_file_after(&self.dir_path, None)?;
+            return Ok(());
+        };
+        if let Some(is_last_written_block) = read_block(
+            &self.dir_path,
+            *file_number,
+            file,
+            self.block_id,
+            &mut self.block,
+        )? {
+            self.is_last_written_block = is_last_written_block;
+        }
+        Ok(())
+    }
+}
+
+impl BlockRead for FollowedBlocks {
+    fn next_block(&mut self) -> io::Result<bool> {
+        if let Some((file_number, file)) = &mut self.file_opt {
+            if self.block_id + 1 < NUM_BLOCKS_PER_FILE {
+                let block_id = self.block_id + 1;
+                let Some(is_last_written_block) = read_block(
+                    &self.dir_path,
+                    *file_number,
+                    file,
+                    block_id,
+                    &mut self.block,
+                )?
+                else {
+                    return Ok(false);
+                };
+                self.block_id = block_id;
+                self.is_last_written_block = is_last_written_block;
+                return Ok(true);
+            }
+        }
+        let Some((file_number, mut file)) =
+            open_file_after(&self.dir_path, self.current_file_number())?
+        else {
+            return Ok(false);
+        };
+        let Some(is_last_written_block) =
+            read_block(&self.dir_path, file_number, &mut file, 0, &mut self.block)?
+        else {
+            return Ok(false);
+        };
+        self.file_opt = Some((file_number, file));
+        self.block_id = 0;
+        self.is_last_written_block = is_last_written_block;
+        Ok(true)
+    }
+
+    fn block(&self) -> &[u8; BLOCK_NUM_BYTES] {
+        &self.block
+    }
+}
+
+impl WalBlockRead for FollowedBlocks {
+    fn location(&self, offset: usize) -> WalLocation {
+        WalLocation {
+            file_number: self.current_file_number().unwrap_or(0),
+            block_id: self.block_id,
+            offset,
+        }
+    }
+
+    fn is_being_written(&self) -> bool {
+        self.is_last_written_block
+    }
+}
+
+/// Opens the first wal file following `file_number_opt`.
+///
+/// Files garbage collected before they could be opened are skipped.
+fn open_file_after(
+    dir_path: &Path,
+    file_number_opt: Option<u64>,
+) -> io::Result<Option<(u64, File)>> {
+    for file_number in list_wal_files(dir_path)? {
+        if !is_after(file_number, file_number_opt) {
+            continue;
+        }
+        match File::open(wal_filepath(dir_path, file_number)) {
+            Ok(file) => return Ok(Some((file_number, file))),
+            Err(io_err) if io_err.kind() == io::ErrorKind::NotFound => {}
+            Err(io_err) => return Err(io_err),
+        }
+    }
+    Ok(None)
+}
+
+fn has_file_after(dir_path: &Path, file_number_opt: Option<u64>) -> io::Result<bool> {
+    Ok(list_wal_files(dir_path)?
+        .into_iter()
+        .any(|file_number| is_after(file_number, file_number_opt)))
+}
+
+fn is_after(file_number: u64, file_number_opt: Option<u64>) -> bool {
+    file_number_opt
+        .map(|last_file_number| file_number > last_file_number)
+        .unwrap_or(true)
+}
+
+/// Reads a block, unless the file is not that long yet.
+///
+/// Returns whether the writer had not started writing the following block yet. This is checked
+/// before reading the block, so that it cannot have changed in between.
+fn read_block(
+    dir_path: &Path,
+    file_number: u64,
+    file: &mut File,
+    block_id: usize,
+    block: &mut [u8; BLOCK_NUM_BYTES],
+) -> io::Result<Option<bool>> {
+    let is_last_written_block = if block_id + 1 == NUM_BLOCKS_PER_FILE {
+        !has_file_after(dir_path, Some(file_number))?
+    } else {
+        let mut header = [0u8; HEADER_LEN];
+        let next_block_offset = (block_id + 1) * BLOCK_NUM_BYTES;
+        !read_at(file, next_block_offset, &mut header)? || header == [0u8; HEADER_LEN]
+    };
+    if !read_at(file, block_id * BLOCK_NUM_BYTES, &mut block[..])? {
+        return Ok(None);
+    }
+    Ok(Some(is_last_written_block))
+}
+
+fn read_at(file: &mut File, offset: usize, buf: &mut [u8]) -> io::Result<bool> {
+    file.seek(SeekFrom::Start(offset as u64))?;
+    match file.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(io_err) if io_err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(io_err) => Err(io_err),
+    }
+}
@@ -0,0 +1,50 @@
+//! Read-only inspection of wal directories, for debugging and offline tools.
+//!
+//! Unlike [`MultiRecordLog::open`](crate::MultiRecordLog::open), nothing in this module
+//! creates, writes or deletes files.
+
+mod follower;
+mod record;
+mod replay;
+mod scanner;
+mod stats;
+#[cfg(test)]
+mod tests;
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub use self::follower::WalFollower;
+pub use self::record::WalRecord;
+pub use self::replay::{ReplayedQueue, ReplayedRecord, ReplayedWal};
+pub use self::scanner::{
+    FrameInfo, RecordInfo, WalEntry, WalIssue, WalIssueKind, WalLocation, WalScanner,
+};
+pub use self::stats::{file_stats, FileStats};
+pub use crate::frame::{FrameStatus, FrameType};
+use crate::rolling::filename_to_position;
+
+/// Returns the numbers of the wal files of the directory, sorted.
+pub fn list_wal_files(dir_path: &Path) -> io::Result<Vec<u64>> {
+    let mut file_numbers = Vec::new();
+    for dir_entry_res in std::fs::read_dir(dir_path)? {
+        let dir_entry = dir_entry_res?;
+        if !dir_entry.file_type()?.is_file() {
+            continue;
+        }
+        if let Some(file_number) = dir_entry
+            .file_name()
+            .to_str()
+            .and_then(filename_to_position)
+        {
+            file_numbers.push(file_number);
+        }
+    }
+    file_numbers.sort_unstable();
+    Ok(file_numbers)
+}
+
+/// Returns the path of a wal file.
+pub fn wal_filepath(dir_path: &Path, file_number: u64) -> PathBuf {
+    dir_path.join(format!("wal-{file_number:020}"))
+}
@@ -0,0 +1,115 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::record::MultiPlexedRecord;
+use crate::DurabilityClass;
+
+/// Owned version of a record of the wal, as decoded by a [`WalScanner`](super::WalScanner).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum WalRecord {
+    /// Records appended to a queue, with their positions.
+    AppendRecords {
+        queue: String,
+        records: Vec<(u64, Vec<u8>)>,
+    },
+    /// Truncation of a queue, up to and including `up_to`.
+    Truncate {
+        queue: String,
+        up_to: u64,
+    },
+    /// Next position of a queue, creating it if it does not exist.
+    RecordPosition {
+        queue: String,
+        position: u64,
+    },
+    DeleteQueue {
+        queue: String,
+        position: u64,
+    },
+    DurabilityClass {
+        queue: String,
+        class: DurabilityClass,
+    },
+}
+
+impl WalRecord {
+    pub fn queue(&self) -> &str {
+        match self {
+            WalRecord::AppendRecords { queue, .. }
+            | WalRecord::Truncate { queue, .. }
+            | WalRecord::RecordPosition { queue, .. }
+            | WalRecord::DeleteQueue { queue, .. }
+            | WalRecord::DurabilityClass { queue, .. } => queue,
+        }
+    }
+
+    /// Converts a record read from the wal. Returns `None` if the records it carries are
+    /// corrupted.
+    pub(crate) fn from_multiplexed(record: MultiPlexedRecord) -> Option<WalRecord> {
+        let wal_record = match record {
+            MultiPlexedRecord::AppendRecords { queue, records, .. } => {
+                let records = records
+                    .map(|record_res| {
+                        record_res.map(|(position, payload)| (position, payload.to_vec()))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .ok()?;
+                WalRecord::AppendRecords {
+                    queue: queue.to_string(),
+                    records,
+                }
+            }
+            MultiPlexedRecord::Truncate {
+                queue,
+                truncate_range,
+            } => WalRecord::Truncate {
+                queue: queue.to_string(),
+                up_to: truncate_range.end,
+            },
+            MultiPlexedRecord::RecordPosition { queue, position } => WalRecord::RecordPosition {
+                queue: queue.to_string(),
+                position,
+            },
+            MultiPlexedRecord::DeleteQueue { queue, position } => WalRecord::DeleteQueue {
+                queue: queue.to_string(),
+                position,
+            },
+            MultiPlexedRecord::DurabilityClass { queue, class } => WalRecord::DurabilityClass {
+                queue: queue.to_string(),
+                class,
+            },
+        };
+        Some(wal_record)
+    }
+}
+
+impl fmt::Display for WalRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WalRecord::AppendRecords { queue, records } => {
+                write!(
+                    f,
+                    "AppendRecords queue={queue} num_records={}",
+                    records.len()
+                )?;
+                if let (Some((first, _)), Some((last, _))) = (records.first(), records.last()) {
+                    write!(f, " positions={first}..={last}")?;
+                }
+                Ok(())
+            }
+            WalRecord::Truncate { queue, up_to } => {
+                write!(f, "Truncate queue={queue} up_to={up_to}")
+            }
+            WalRecord::RecordPosition { queue, position } => {
+                write!(f, "RecordPosition queue={queue} position={position}")
+            }
+            WalRecord::DeleteQueue { queue, position } => {
+                write!(f, "DeleteQueue queue={queue} position={position}")
+            }
+            WalRecord::DurabilityClass { queue, class } => {
+                write!(f, "DurabilityClass queue={queue} class={class:?}")
+            }
+        }
+    }
+}
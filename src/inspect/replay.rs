@@ -0,0 +1,153 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::path::Path;
+
+use super::{WalEntry, WalIssue, WalRecord, WalScanner};
+use crate::DurabilityClass;
+
+/// A record held by a queue after replaying the wal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayedRecord {
+    pub position: u64,
+    pub payload: Vec<u8>,
+    /// Number of the wal file the record was read from.
+    pub file_number: u64,
+}
+
+/// State of a queue after replaying the wal.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReplayedQueue {
+    start_position: u64,
+    pub durability_class: DurabilityClass,
+    pub records: VecDeque<ReplayedRecord>,
+}
+
+impl ReplayedQueue {
+    fn with_next_position(next_position: u64) -> ReplayedQueue {
+        ReplayedQueue {
+            start_position: next_position,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the position the next record appended to the queue would get.
+    pub fn next_position(&self) -> u64 {
+        self.records
+            .back()
+            .map(|record| record.position + 1)
+            .unwrap_or(self.start_position)
+    }
+
+    fn append(&mut self, position: u64, payload: Vec<u8>, file_number: u64) -> bool {
+        if position < self.next_position() {
+            return false;
+        }
+        if self.start_position == 0 && self.records.is_empty() {
+            self.start_position = position;
+        }
+        self.records.push_back(ReplayedRecord {
+            position,
+            payload,
+            file_number,
+        });
+        true
+    }
+
+    fn truncate(&mut self, up_to: u64) {
+        if self.start_position > up_to {
+            return;
+        }
+        while self
+            .records
+            .front()
+            .map(|record| record.position <= up_to)
+            .unwrap_or(false)
+        {
+            self.records.pop_front();
+        }
+        self.start_position = up_to + 1;
+    }
+}
+
+/// State of the queues rebuilt from the records of a wal directory, the same way
+/// [`MultiRecordLog::open`](crate::MultiRecordLog::open) does, without modifying the
+/// directory.
+///
+/// Unlike opening the log, replaying does not fail on records appended before the end of their
+/// queue: they are skipped and reported.
+#[derive(Clone, Debug, Default)]
+pub struct ReplayedWal {
+    pub queues: BTreeMap<String, ReplayedQueue>,
+    /// Issues found while scanning the wal. The data they affect was skipped.
+    pub issues: Vec<WalIssue>,
+    /// Appended records skipped because their position came before the end of their queue,
+    /// as `(queue, position)`.
+    pub rejected_records: Vec<(String, u64)>,
+    /// Number of appended records read, whether they are still live or not.
+    pub num_appended_records: usize,
+}
+
+impl ReplayedWal {
+    /// Scans and replays every wal file of the directory.
+    pub fn replay(dir_path: &Path) -> io::Result<ReplayedWal> {
+        let mut replayed_wal = ReplayedWal::default();
+        for entry_res in WalScanner::open(dir_path)? {
+            match entry_res? {
+                WalEntry::Record(record_info) => {
+                    replayed_wal.apply(record_info.record, record_info.location.file_number);
+                }
+                WalEntry::Issue(issue) => replayed_wal.issues.push(issue),
+                WalEntry::Frame(_) => {}
+            }
+        }
+        Ok(replayed_wal)
+    }
+
+    /// Applies a record read from the wal file `file_number`.
+    pub fn apply(&mut self, record: WalRecord, file_number: u64) {
+        match record {
+            WalRecord::AppendRecords { queue, records } => {
+                let Some((first_position, _)) = records.first() else {
+                    return;
+                };
+                let replayed_queue = self
+                    .queues
+                    .entry(queue.clone())
+                    .or_insert_with(|| ReplayedQueue::with_next_position(*first_position));
+                for (position, payload) in records {
+                    self.num_appended_records += 1;
+                    if !replayed_queue.append(position, payload, file_number) {
+                        self.rejected_records.push((queue.clone(), position));
+                    }
+                }
+            }
+            WalRecord::Truncate { queue, up_to } => {
+                if let Some(replayed_queue) = self.queues.get_mut(&queue) {
+                    replayed_queue.truncate(up_to);
+                }
+            }
+            WalRecord::RecordPosition { queue, position } => {
+                let is_up_to_date = self
+                    .queues
+                    .get(&queue)
+                    .map(|replayed_queue| {
+                        replayed_queue.records.is_empty()
+                            && replayed_queue.next_position() == position
+                    })
+                    .unwrap_or(false);
+                if !is_up_to_date {
+                    self.queues
+                        .insert(queue, ReplayedQueue::with_next_position(position));
+                }
+            }
+            WalRecord::DeleteQueue { queue, .. } => {
+                self.queues.remove(&queue);
+            }
+            WalRecord::DurabilityClass { queue, class } => {
+                if let Some(replayed_queue) = self.queues.get_mut(&queue) {
+                    replayed_queue.durability_class = class;
+                }
+            }
+        }
+    }
+}
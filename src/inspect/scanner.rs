@@ -0,0 +1,378 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use super::{list_wal_files, wal_filepath, WalRecord};
+use crate::error::ReadRecordError;
+use crate::frame::{DecodedFrame, FrameStatus, FrameType, HEADER_LEN};
+use crate::record::MultiPlexedRecord;
+use crate::recordlog::{ReadObserver, RecordIssue, RecordReader};
+use crate::{BlockRead, BLOCK_NUM_BYTES};
+
+/// Position of a frame or a record within a wal directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct WalLocation {
+    pub file_number: u64,
+    pub block_id: usize,
+    /// Offset within the block.
+    pub offset: usize,
+}
+
+impl WalLocation {
+    /// Offset within the file.
+    pub fn file_offset(&self) -> usize {
+        self.block_id * BLOCK_NUM_BYTES + self.offset
+    }
+}
+
+impl fmt::Display for WalLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "wal-{:020} block={} offset={}",
+            self.file_number, self.block_id, self.offset
+        )
+    }
+}
+
+/// A frame, as found in a wal file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FrameInfo {
+    pub location: WalLocation,
+    /// `None` if the header is corrupted.
+    pub frame_type: Option<FrameType>,
+    /// Length of the payload, excluding the header.
+    pub len: usize,
+    pub status: FrameStatus,
+}
+
+/// A record reassembled from its frames.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RecordInfo {
+    /// Location of the first frame of the record.
+    pub location: WalLocation,
+    /// Length of the record, including its record header.
+    pub len: usize,
+    /// `(epoch, sequence)` of the record, if it carries a record header.
+    pub record_id: Option<(u32, u64)>,
+    pub record: WalRecord,
+}
+
+/// Something that makes data unreadable, or that the log skips when it is opened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum WalIssueKind {
+    /// The frame header could not be decoded. The rest of the block is unreadable.
+    CorruptedHeader,
+    /// The checksum of a frame payload does not match its header.
+    BadFrameChecksum,
+    /// A frame header announces a payload going past the end of its block. The rest of the
+    /// block is unreadable.
+    FrameSpansBlocks,
+    /// A `Middle` or `Last` frame that does not follow the beginning of a record.
+    OrphanedFrame(FrameType),
+    /// A record whose `Last` frame was never found.
+    IncompleteRecord,
+    /// The checksum of a reassembled record does not match its record header.
+    BadRecordChecksum,
+    /// A record whose id does not come after the id of the previous record: a leftover of an
+    /// earlier write.
+    StaleRecord,
+    /// A record that could not be deserialized.
+    UndecodableRecord,
+}
+
+impl fmt::Display for WalIssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WalIssueKind::CorruptedHeader => f.write_str("corrupted frame header"),
+            WalIssueKind::BadFrameChecksum => f.write_str("bad frame checksum"),
+            WalIssueKind::FrameSpansBlocks => f.write_str("frame spans block boundary"),
+            WalIssueKind::OrphanedFrame(frame_type) => write!(f, "orphaned {frame_type:?} frame"),
+            WalIssueKind::IncompleteRecord => f.write_str("incomplete record"),
+            WalIssueKind::BadRecordChecksum => f.write_str("bad record checksum"),
+            WalIssueKind::StaleRecord => f.write_str("stale record"),
+            WalIssueKind::UndecodableRecord => f.write_str("undecodable record"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct WalIssue {
+    /// Location of the frame, or of the first frame of the record.
+    pub location: WalLocation,
+    pub kind: WalIssueKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum WalEntry {
+    Frame(FrameInfo),
+    Record(RecordInfo),
+    /// Reported right after the frame or record it is about.
+    Issue(WalIssue),
+}
+
+/// Blocks of wal files read by the inspection tools.
+pub(super) trait WalBlockRead: BlockRead {
+    /// Location of an offset within the current block.
+    fn location(&self, offset: usize) -> WalLocation;
+
+    /// Returns true if the writer may still be writing the current block. A corrupted frame
+    /// followed by nothing is then considered to be partially written.
+    fn is_being_written(&self) -> bool;
+}
+
+/// Returns true if nothing follows the part of a corrupted frame that could be decoded.
+fn is_partially_written(block: &[u8; BLOCK_NUM_BYTES], frame: &DecodedFrame) -> bool {
+    let decoded_end = match frame.status {
+        FrameStatus::BadChecksum => frame.offset + HEADER_LEN + frame.len,
+        FrameStatus::Valid | FrameStatus::CorruptedHeader | FrameStatus::SpansBlocks => {
+            frame.offset + HEADER_LEN
+        }
+    };
+    block[decoded_end.min(BLOCK_NUM_BYTES)..]
+        .iter()
+        .all(|byte| *byte == 0)
+}
+
+/// Turns the frames read by a [`RecordReader`], the records it reads, and what it drops, into
+/// entries.
+#[derive(Default)]
+pub(super) struct EntryCollector {
+    pending_entries: VecDeque<WalEntry>,
+    // Location of the last frame read, and of the first frame of the record being assembled.
+    frame_location: Option<WalLocation>,
+    record_start: Option<WalLocation>,
+}
+
+impl EntryCollector {
+    pub fn pop_entry(&mut self) -> Option<WalEntry> {
+        self.pending_entries.pop_front()
+    }
+
+    /// Reads the next record, collecting the entries found on the way.
+    ///
+    /// Returns false if nothing more can be read from the current block.
+    pub fn read_next<R: WalBlockRead + Unpin>(
+        &mut self,
+        record_reader: &mut RecordReader<R>,
+    ) -> io::Result<bool> {
+        match record_reader.go_next_observed(self) {
+            Ok(true) => {
+                self.push_record(record_reader);
+                Ok(true)
+            }
+            Ok(false) => Ok(false),
+            // The corruption was reported to the collector.
+            Err(ReadRecordError::Corruption) => Ok(true),
+            Err(ReadRecordError::IoError(io_err)) => Err(io_err),
+        }
+    }
+
+    /// Reports the record being assembled, if any, as incomplete.
+    pub fn finish<R: WalBlockRead + Unpin>(&mut self, record_reader: &RecordReader<R>) {
+        if record_reader.is_within_record() {
+            self.push_issue(self.record_start, WalIssueKind::IncompleteRecord);
+        }
+    }
+
+    fn push_record<R: WalBlockRead + Unpin>(&mut self, record_reader: &RecordReader<R>) {
+        let Some(location) = self.record_start else {
+            return;
+        };
+        let Some(record) = record_reader
+            .record::<MultiPlexedRecord>()
+            .and_then(WalRecord::from_multiplexed)
+        else {
+            self.push_issue(Some(location), WalIssueKind::UndecodableRecord);
+            return;
+        };
+        self.pending_entries.push_back(WalEntry::Record(RecordInfo {
+            location,
+            len: record_reader.record_len(),
+            record_id: record_reader.record_id(),
+            record,
+        }));
+    }
+
+    fn push_issue(&mut self, location_opt: Option<WalLocation>, kind: WalIssueKind) {
+        if let Some(location) = location_opt {
+            self.pending_entries
+                .push_back(WalEntry::Issue(WalIssue { location, kind }));
+        }
+    }
+}
+
+impl<R: WalBlockRead> ReadObserver<R> for EntryCollector {
+    fn frame(&mut self, reader: &R, frame: &DecodedFrame) -> ControlFlow<()> {
+        let issue_kind_opt = match frame.status {
+            FrameStatus::Valid => None,
+            FrameStatus::CorruptedHeader => Some(WalIssueKind::CorruptedHeader),
+            FrameStatus::BadChecksum => Some(WalIssueKind::BadFrameChecksum),
+            FrameStatus::SpansBlocks => Some(WalIssueKind::FrameSpansBlocks),
+        };
+        if issue_kind_opt.is_some()
+            && reader.is_being_written()
+            && is_partially_written(reader.block(), frame)
+        {
+            return ControlFlow::Break(());
+        }
+        let location = reader.location(frame.offset);
+        self.pending_entries.push_back(WalEntry::Frame(FrameInfo {
+            location,
+            frame_type: frame.frame_type,
+            len: frame.len,
+            status: frame.status,
+        }));
+        if let Some(issue_kind) = issue_kind_opt {
+            self.push_issue(Some(location), issue_kind);
+        }
+        self.frame_location = Some(location);
+        ControlFlow::Continue(())
+    }
+
+    fn record_started(&mut self) {
+        self.record_start = self.frame_location;
+    }
+
+    fn issue(&mut self, issue: RecordIssue) {
+        let (location_opt, kind) = match issue {
+            RecordIssue::OrphanedFrame(frame_type) => {
+                (self.frame_location, WalIssueKind::OrphanedFrame(frame_type))
+            }
+            RecordIssue::IncompleteRecord => (self.record_start, WalIssueKind::IncompleteRecord),
+            RecordIssue::BadRecordChecksum => (self.record_start, WalIssueKind::BadRecordChecksum),
+            RecordIssue::StaleRecord => (self.record_start, WalIssueKind::StaleRecord),
+        };
+        self.push_issue(location_opt, kind);
+    }
+}
+
+/// Blocks of the wal files of a directory, read in order.
+///
+/// Until the first block is loaded, the current block is an empty block preceding the first
+/// file.
+struct ScannedBlocks {
+    dir_path: PathBuf,
+    remaining_file_numbers: VecDeque<u64>,
+    file_opt: Option<(u64, File)>,
+    // Number of blocks read from the current file. The current block is the last one read.
+    num_blocks_read: usize,
+    block: Box<[u8; BLOCK_NUM_BYTES]>,
+}
+
+impl BlockRead for ScannedBlocks {
+    fn next_block(&mut self) -> io::Result<bool> {
+        loop {
+            if let Some((_, file)) = &mut self.file_opt {
+                match file.read_exact(&mut self.block[..]) {
+                    Ok(()) => {
+                        self.num_blocks_read += 1;
+                        return Ok(true);
+                    }
+                    Err(io_err) if io_err.kind() == io::ErrorKind::UnexpectedEof => {}
+                    Err(io_err) => {
+                        self.file_opt = None;
+                        return Err(io_err);
+                    }
+                }
+            }
+            let Some(file_number) = self.remaining_file_numbers.pop_front() else {
+                self.file_opt = None;
+                return Ok(false);
+            };
+            // The file is only set once opened, so that an error moves on to the next file.
+            self.file_opt = None;
+            let file = File::open(wal_filepath(&self.dir_path, file_number))?;
+            self.file_opt = Some((file_number, file));
+            self.num_blocks_read = 0;
+        }
+    }
+
+    fn block(&self) -> &[u8; BLOCK_NUM_BYTES] {
+        &self.block
+    }
+}
+
+impl WalBlockRead for ScannedBlocks {
+    fn location(&self, offset: usize) -> WalLocation {
+        WalLocation {
+            file_number: self
+                .file_opt
+                .as_ref()
+                .map(|(file_number, _)| *file_number)
+                .unwrap_or(0),
+            block_id: self.num_blocks_read.saturating_sub(1),
+            offset,
+        }
+    }
+
+    fn is_being_written(&self) -> bool {
+        false
+    }
+}
+
+/// Iterates over every frame of a wal directory, and the records they carry, in order.
+///
+/// Frames and records are read the same way
+/// [`MultiRecordLog::open`](crate::MultiRecordLog::open) reads them. Unlike it, the scanner only
+/// reads files: it does not create the first file of an empty directory, nor positions a
+/// writer. It does not stop at the first corruption either, and keeps going until the end of
+/// the last file.
+pub struct WalScanner {
+    record_reader: RecordReader<ScannedBlocks>,
+    collector: EntryCollector,
+    finished: bool,
+}
+
+impl WalScanner {
+    pub fn open(dir_path: &Path) -> io::Result<WalScanner> {
+        let file_numbers = list_wal_files(dir_path)?;
+        let blocks = ScannedBlocks {
+            dir_path: dir_path.to_path_buf(),
+            remaining_file_numbers: file_numbers.into(),
+            file_opt: None,
+            num_blocks_read: 0,
+            block: Box::new([0u8; BLOCK_NUM_BYTES]),
+        };
+        Ok(WalScanner {
+            record_reader: RecordReader::open(blocks),
+            collector: EntryCollector::default(),
+            finished: false,
+        })
+    }
+}
+
+impl Iterator for WalScanner {
+    type Item = io::Result<WalEntry>;
+
+    fn next(&mut self) -> Option<io::Result<WalEntry>> {
+        loop {
+            if let Some(entry) = self.collector.pop_entry() {
+                return Some(Ok(entry));
+            }
+            if self.finished {
+                return None;
+            }
+            let read_res = self
+                .collector
+                .read_next(&mut self.record_reader)
+                .and_then(|has_read| {
+                    // Whatever was not written in this block is simply skipped.
+                    Ok(has_read || self.record_reader.skip_to_next_block()?)
+                });
+            match read_res {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.finished = true;
+                    self.collector.finish(&self.record_reader);
+                }
+                Err(io_err) => return Some(Err(io_err)),
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::{list_wal_files, ReplayedWal, WalEntry, WalRecord, WalScanner};
+use crate::frame::HEADER_LEN;
+use crate::BLOCK_NUM_BYTES;
+
+/// How the space of a wal file is used.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct FileStats {
+    pub file_number: u64,
+    /// Bytes taken by frames, headers included.
+    pub written_bytes: usize,
+    /// Payload bytes of the records still live, per queue. The queues listed here prevent the
+    /// file, and every file after it, from being garbage collected.
+    pub live_bytes: BTreeMap<String, usize>,
+    /// Payload bytes of the records appended in the file that were truncated or deleted since.
+    pub truncated_bytes: usize,
+    /// Space left unused at the end of blocks, because it was too small to hold a frame.
+    pub padding_bytes: usize,
+}
+
+impl FileStats {
+    /// Returns the queues holding live records in the file.
+    pub fn pinned_by(&self) -> impl Iterator<Item = &str> {
+        self.live_bytes.keys().map(String::as_str)
+    }
+
+    pub fn total_live_bytes(&self) -> usize {
+        self.live_bytes.values().sum()
+    }
+}
+
+/// Computes the statistics of every wal file of the directory, by replaying their records.
+pub fn file_stats(dir_path: &Path) -> io::Result<Vec<FileStats>> {
+    let mut stats_per_file: BTreeMap<u64, FileStats> = list_wal_files(dir_path)?
+        .into_iter()
+        .map(|file_number| {
+            let file_stats = FileStats {
+                file_number,
+                ..Default::default()
+            };
+            (file_number, file_stats)
+        })
+        .collect();
+    let mut appended_bytes_per_file: BTreeMap<u64, usize> = BTreeMap::new();
+    // Bytes taken by frames in each block, as `(file_number, block_id)`.
+    let mut used_bytes_per_block: BTreeMap<(u64, usize), usize> = BTreeMap::new();
+    let mut replayed_wal = ReplayedWal::default();
+    for entry_res in WalScanner::open(dir_path)? {
+        match entry_res? {
+            WalEntry::Frame(frame_info) => {
+                let location = frame_info.location;
+                let frame_num_bytes = HEADER_LEN + frame_info.len;
+                *used_bytes_per_block
+                    .entry((location.file_number, location.block_id))
+                    .or_default() += frame_num_bytes;
+                stats_per_file
+                    .entry(location.file_number)
+                    .or_default()
+                    .written_bytes += frame_num_bytes;
+            }
+            WalEntry::Record(record_info) => {
+                let file_number = record_info.location.file_number;
+                if let WalRecord::AppendRecords { records, .. } = &record_info.record {
+                    *appended_bytes_per_file.entry(file_number).or_default() += records
+                        .iter()
+                        .map(|(_, payload)| payload.len())
+                        .sum::<usize>();
+                }
+                replayed_wal.apply(record_info.record, file_number);
+            }
+            WalEntry::Issue(_) => {}
+        }
+    }
+    // The space left at the end of the last written block is not padding, as the writer will
+    // fill it.
+    let last_block_opt = used_bytes_per_block.keys().next_back().copied();
+    for (block, used_bytes) in &used_bytes_per_block {
+        if Some(*block) != last_block_opt {
+            stats_per_file.entry(block.0).or_default().padding_bytes +=
+                BLOCK_NUM_BYTES.saturating_sub(*used_bytes);
+        }
+    }
+    for (queue, replayed_queue) in &replayed_wal.queues {
+        for record in &replayed_queue.records {
+            *stats_per_file
+                .entry(record.file_number)
+                .or_default()
+                .live_bytes
+                .entry(queue.clone())
+                .or_default() += record.payload.len();
+        }
+    }
+    for (file_number, file_stats) in stats_per_file.iter_mut() {
+        file_stats.file_number = *file_number;
+        let appended_bytes = appended_bytes_per_file
+            .get(file_number)
+            .copied()
+            .unwrap_or(0);
+        file_stats.truncated_bytes = appended_bytes.saturating_sub(file_stats.total_live_bytes());
+    }
+    Ok(stats_per_file.into_values().collect())
+}
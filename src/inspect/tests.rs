@@ -0,0 +1,413 @@
+use std::fs::OpenOptions;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+use super::*;
+use crate::MultiRecordLog;
+
+fn scan(dir_path: &Path) -> Vec<WalEntry> {
+    WalScanner::open(dir_path)
+        .unwrap()
+        .collect::<std::io::Result<Vec<_>>>()
+        .unwrap()
+}
+
+fn records(entries: &[WalEntry]) -> Vec<&WalRecord> {
+    entries
+        .iter()
+        .filter_map(|entry| match entry {
+            WalEntry::Record(record_info) => Some(&record_info.record),
+            WalEntry::Frame(_) | WalEntry::Issue(_) => None,
+        })
+        .collect()
+}
+
+fn frames(entries: &[WalEntry]) -> Vec<&FrameInfo> {
+    entries
+        .iter()
+        .filter_map(|entry| match entry {
+            WalEntry::Frame(frame_info) => Some(frame_info),
+            WalEntry::Record(_) | WalEntry::Issue(_) => None,
+        })
+        .collect()
+}
+
+fn issues(entries: &[WalEntry]) -> Vec<WalIssue> {
+    entries
+        .iter()
+        .filter_map(|entry| match entry {
+            WalEntry::Issue(issue) => Some(*issue),
+            WalEntry::Frame(_) | WalEntry::Record(_) => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_scan_empty_directory() {
+    let tempdir = tempfile::tempdir().unwrap();
+    assert!(scan(tempdir.path()).is_empty());
+    // Scanning does not create the first wal file.
+    assert!(list_wal_files(tempdir.path()).unwrap().is_empty());
+}
+
+#[test]
+fn test_scan_records() {
+    let tempdir = tempfile::tempdir().unwrap();
+    {
+        let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        multi_record_log.create_queue("queue").unwrap();
+        multi_record_log
+            .append_records("queue", None, [&b"hello"[..], &b"world"[..]].into_iter())
+            .unwrap();
+        multi_record_log.truncate("queue", ..=0).unwrap();
+        // Spans several frames.
+        let payload = vec![1u8; 40_000];
+        multi_record_log
+            .append_record("queue", None, &payload[..])
+            .unwrap();
+        multi_record_log.delete_queue("queue").unwrap();
+    }
+    let entries = scan(tempdir.path());
+    assert!(frames(&entries)
+        .iter()
+        .all(|frame_info| frame_info.status == FrameStatus::Valid));
+    assert!(issues(&entries).is_empty());
+    assert_eq!(
+        &records(&entries)[..],
+        &[
+            &WalRecord::RecordPosition {
+                queue: "queue".to_string(),
+                position: 0
+            },
+            &WalRecord::AppendRecords {
+                queue: "queue".to_string(),
+                records: vec![(0, b"hello".to_vec()), (1, b"world".to_vec())],
+            },
+            &WalRecord::Truncate {
+                queue: "queue".to_string(),
+                up_to: 0
+            },
+            &WalRecord::AppendRecords {
+                queue: "queue".to_string(),
+                records: vec![(2, vec![1u8; 40_000])],
+            },
+            &WalRecord::DeleteQueue {
+                queue: "queue".to_string(),
+                position: 3
+            },
+        ]
+    );
+    let frame_types: Vec<FrameType> = frames(&entries)
+        .iter()
+        .filter_map(|frame_info| frame_info.frame_type)
+        .collect();
+    assert_eq!(
+        &frame_types,
+        &[
+            // Epoch marker.
+            FrameType::Full,
+            FrameType::Full,
+            FrameType::Full,
+            FrameType::Full,
+            FrameType::First,
+            FrameType::Last,
+            FrameType::Full
+        ]
+    );
+    let last_frame_location = frames(&entries)[5].location;
+    assert_eq!(last_frame_location.file_number, 0);
+    assert_eq!(last_frame_location.block_id, 1);
+    assert_eq!(last_frame_location.offset, 0);
+}
+
+#[test]
+fn test_scan_corrupted_frame() {
+    let tempdir = tempfile::tempdir().unwrap();
+    {
+        let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        multi_record_log.create_queue("queue").unwrap();
+        for _ in 0..3 {
+            multi_record_log
+                .append_record("queue", None, &b"hello"[..])
+                .unwrap();
+        }
+    }
+    let entries = scan(tempdir.path());
+    // After the epoch marker and the creation of the queue.
+    let second_append = frames(&entries)[3].location;
+    {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(wal_filepath(tempdir.path(), 0))
+            .unwrap();
+        // Corrupt the payload of the frame of the second append.
+        file.seek(SeekFrom::Start(second_append.file_offset() as u64 + 20))
+            .unwrap();
+        file.write_all(b"corrupted").unwrap();
+    }
+    let entries = scan(tempdir.path());
+    let statuses: Vec<FrameStatus> = frames(&entries)
+        .iter()
+        .map(|frame_info| frame_info.status)
+        .collect();
+    assert_eq!(
+        &statuses,
+        &[
+            FrameStatus::Valid,
+            FrameStatus::Valid,
+            FrameStatus::Valid,
+            FrameStatus::BadChecksum,
+            FrameStatus::Valid
+        ]
+    );
+    assert_eq!(records(&entries).len(), 3);
+    assert_eq!(
+        &issues(&entries),
+        &[WalIssue {
+            location: second_append,
+            kind: WalIssueKind::BadFrameChecksum
+        }]
+    );
+}
+
+#[test]
+fn test_scan_orphaned_frame() {
+    let tempdir = tempfile::tempdir().unwrap();
+    {
+        let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        multi_record_log.create_queue("queue").unwrap();
+        let payload = vec![1u8; 40_000];
+        multi_record_log
+            .append_record("queue", None, &payload[..])
+            .unwrap();
+        multi_record_log
+            .append_record("queue", None, &b"hello"[..])
+            .unwrap();
+    }
+    let entries = scan(tempdir.path());
+    let first_frame = frames(&entries)[2].location;
+    let last_frame = frames(&entries)[3].location;
+    {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(wal_filepath(tempdir.path(), 0))
+            .unwrap();
+        file.seek(SeekFrom::Start(first_frame.file_offset() as u64 + 100))
+            .unwrap();
+        file.write_all(b"corrupted").unwrap();
+    }
+    let entries = scan(tempdir.path());
+    assert_eq!(
+        &issues(&entries),
+        &[
+            WalIssue {
+                location: first_frame,
+                kind: WalIssueKind::BadFrameChecksum
+            },
+            WalIssue {
+                location: last_frame,
+                kind: WalIssueKind::OrphanedFrame(FrameType::Last)
+            },
+        ]
+    );
+    // The records around the corrupted one are still readable.
+    assert_eq!(records(&entries).len(), 2);
+}
+
+#[test]
+fn test_replay() {
+    let tempdir = tempfile::tempdir().unwrap();
+    {
+        let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        multi_record_log.create_queue("queue").unwrap();
+        multi_record_log
+            .create_queue_with_class("fsynced", crate::DurabilityClass::AlwaysFsync)
+            .unwrap();
+        multi_record_log.create_queue("deleted").unwrap();
+        multi_record_log
+            .append_records("queue", None, [&b"a"[..], &b"b"[..], &b"c"[..]].into_iter())
+            .unwrap();
+        multi_record_log.truncate("queue", ..=0).unwrap();
+        multi_record_log.truncate("fsynced", ..=4).unwrap();
+        multi_record_log
+            .append_record("deleted", None, &b"d"[..])
+            .unwrap();
+        multi_record_log.delete_queue("deleted").unwrap();
+    }
+    let replayed_wal = ReplayedWal::replay(tempdir.path()).unwrap();
+    assert!(replayed_wal.issues.is_empty());
+    assert!(replayed_wal.rejected_records.is_empty());
+    assert_eq!(replayed_wal.num_appended_records, 4);
+    assert_eq!(
+        replayed_wal.queues.keys().collect::<Vec<_>>(),
+        &["fsynced", "queue"]
+    );
+    let queue = &replayed_wal.queues["queue"];
+    assert_eq!(queue.next_position(), 3);
+    assert_eq!(
+        queue
+            .records
+            .iter()
+            .map(|record| (record.position, &record.payload[..], record.file_number))
+            .collect::<Vec<_>>(),
+        &[(1, &b"b"[..], 0), (2, &b"c"[..], 0)]
+    );
+    let fsynced = &replayed_wal.queues["fsynced"];
+    assert!(fsynced.records.is_empty());
+    assert_eq!(fsynced.next_position(), 5);
+    assert_eq!(
+        fsynced.durability_class,
+        crate::DurabilityClass::AlwaysFsync
+    );
+}
+
+#[test]
+fn test_replay_rejected_record() {
+    let mut replayed_wal = ReplayedWal::default();
+    replayed_wal.apply(
+        WalRecord::AppendRecords {
+            queue: "queue".to_string(),
+            records: vec![(3, b"a".to_vec()), (4, b"b".to_vec())],
+        },
+        0,
+    );
+    replayed_wal.apply(
+        WalRecord::AppendRecords {
+            queue: "queue".to_string(),
+            records: vec![(4, b"c".to_vec()), (5, b"d".to_vec())],
+        },
+        1,
+    );
+    assert_eq!(&replayed_wal.rejected_records, &[("queue".to_string(), 4)]);
+    let queue = &replayed_wal.queues["queue"];
+    assert_eq!(queue.next_position(), 6);
+    assert_eq!(queue.records.len(), 3);
+    assert_eq!(queue.records[2].file_number, 1);
+}
+
+fn appended_payloads(entries: &[WalEntry]) -> Vec<Vec<u8>> {
+    records(entries)
+        .into_iter()
+        .flat_map(|record| match record {
+            WalRecord::AppendRecords { records, .. } => {
+                records.iter().map(|(_, payload)| payload.clone()).collect()
+            }
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_follow() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+    let mut follower = WalFollower::open(tempdir.path()).unwrap();
+    // Nothing was written yet.
+    assert!(follower.poll().unwrap().is_empty());
+    assert!(follower.poll().unwrap().is_empty());
+    multi_record_log.create_queue("queue").unwrap();
+    multi_record_log
+        .append_record("queue", None, &b"hello"[..])
+        .unwrap();
+    let entries = follower.poll().unwrap();
+    assert_eq!(records(&entries).len(), 2);
+    assert_eq!(appended_payloads(&entries), &[b"hello".to_vec()]);
+    assert!(follower.poll().unwrap().is_empty());
+    // Fills more than a file, so that the log rolls to new files.
+    let payload = vec![1u8; 40_000];
+    let mut num_appended_records = 0;
+    while list_wal_files(tempdir.path()).unwrap().len() < 3 {
+        multi_record_log
+            .append_record("queue", None, &payload[..])
+            .unwrap();
+        num_appended_records += 1;
+        if num_appended_records % 2 == 0 {
+            assert_eq!(appended_payloads(&follower.poll().unwrap()).len(), 2);
+        }
+    }
+    let entries = follower.poll().unwrap();
+    assert_eq!(appended_payloads(&entries).len(), num_appended_records % 2);
+    assert!(issues(&entries).is_empty());
+    multi_record_log.delete_queue("queue").unwrap();
+    let entries = follower.poll().unwrap();
+    assert!(matches!(
+        &records(&entries)[..],
+        &[WalRecord::DeleteQueue { .. }]
+    ));
+}
+
+#[test]
+fn test_follow_partially_written_frame() {
+    let tempdir = tempfile::tempdir().unwrap();
+    {
+        let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        multi_record_log.create_queue("queue").unwrap();
+        multi_record_log
+            .append_record("queue", None, &b"hello"[..])
+            .unwrap();
+        multi_record_log
+            .append_record("queue", None, &b"world"[..])
+            .unwrap();
+    }
+    let entries = scan(tempdir.path());
+    let last_frame = *frames(&entries).last().unwrap();
+    let wal_file = wal_filepath(tempdir.path(), 0);
+    let written_bytes = std::fs::read(&wal_file).unwrap();
+    // Simulates the writer being in the middle of writing the last frame.
+    let mut partially_written_bytes = written_bytes.clone();
+    let frame_end = last_frame.location.file_offset() + 7 + last_frame.len;
+    for byte in &mut partially_written_bytes[frame_end - 10..frame_end] {
+        *byte = 0;
+    }
+    std::fs::write(&wal_file, &partially_written_bytes).unwrap();
+    let mut follower = WalFollower::open(tempdir.path()).unwrap();
+    let entries = follower.poll().unwrap();
+    assert!(issues(&entries).is_empty());
+    assert_eq!(appended_payloads(&entries), &[b"hello".to_vec()]);
+    std::fs::write(&wal_file, &written_bytes).unwrap();
+    let entries = follower.poll().unwrap();
+    assert!(issues(&entries).is_empty());
+    assert_eq!(appended_payloads(&entries), &[b"world".to_vec()]);
+}
+
+#[test]
+fn test_file_stats() {
+    let tempdir = tempfile::tempdir().unwrap();
+    {
+        let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        multi_record_log.create_queue("queue").unwrap();
+        multi_record_log.create_queue("small").unwrap();
+        multi_record_log
+            .append_record("small", None, &b"hello"[..])
+            .unwrap();
+        let payload = vec![1u8; 40_000];
+        for _ in 0..4 {
+            multi_record_log
+                .append_record("queue", None, &payload[..])
+                .unwrap();
+        }
+        multi_record_log.truncate("queue", ..=1).unwrap();
+    }
+    let stats = file_stats(tempdir.path()).unwrap();
+    assert_eq!(stats.len(), 2);
+    let first_file = &stats[0];
+    assert_eq!(first_file.file_number, 0);
+    assert_eq!(first_file.truncated_bytes, 80_000);
+    assert_eq!(first_file.live_bytes["small"], 5);
+    assert_eq!(
+        first_file.pinned_by().collect::<Vec<_>>(),
+        &["queue", "small"]
+    );
+    // The first file is full: every byte is either a frame or padding.
+    assert_eq!(
+        first_file.written_bytes + first_file.padding_bytes,
+        crate::rolling::NUM_BLOCKS_PER_FILE * crate::BLOCK_NUM_BYTES
+    );
+    assert!(
+        first_file.padding_bytes < crate::frame::HEADER_LEN * crate::rolling::NUM_BLOCKS_PER_FILE
+    );
+    let total_live_bytes: usize = stats.iter().map(FileStats::total_live_bytes).sum();
+    assert_eq!(total_live_bytes, 80_005);
+    assert_eq!(stats[1].truncated_bytes, 0);
+    assert_eq!(stats[1].padding_bytes, 0);
+}
@@ -1,21 +1,36 @@
 use std::borrow::Cow;
 
+use bytes::Bytes;
+
+mod background_persist;
 mod block_read_write;
+mod durability;
 
 pub use block_read_write::{BlockRead, BlockWrite, BLOCK_NUM_BYTES};
 pub mod error;
 mod frame;
+pub mod inspect;
+mod listener;
 mod mem;
 mod multi_record_log;
+mod options;
 mod persist_policy;
+mod reader;
 mod record;
 mod recordlog;
 mod rolling;
+mod stats;
 
-pub use mem::{QueueSummary, QueuesSummary};
+pub use durability::DurabilityWatcher;
+pub use listener::LogListener;
+pub use mem::{GlobalSummary, QueueSummary, QueuesSummary};
 pub use multi_record_log::MultiRecordLog;
+pub use options::{GcAction, MultiRecordLogOptions};
 pub(crate) use persist_policy::PersistState;
-pub use persist_policy::{PersistAction, PersistPolicy};
+pub use persist_policy::{DurabilityClass, PersistAction, PersistPolicy};
+pub use reader::LogReader;
+pub use rolling::{FileIndex, QueueFileRange};
+pub use stats::{IoStats, LatencyHistogram, LATENCY_BUCKETS};
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Record<'a> {
@@ -32,17 +47,109 @@ impl<'a> Record<'a> {
     }
 }
 
+/// A record that does not borrow the log.
+///
+/// The payload shares the memory of the queue, and cloning it does not copy it. It stays valid
+/// after the record is truncated or its queue deleted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OwnedRecord {
+    pub position: u64,
+    pub payload: Bytes,
+}
+
+/// Records read from a queue within limits, returned by
+/// [`MultiRecordLog::range_with_limits`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordPage {
+    pub records: Vec<OwnedRecord>,
+    /// Position to read the next page from.
+    pub next_position: u64,
+    /// Whether the queue holds no record at or after `next_position`.
+    pub end_reached: bool,
+}
+
+impl RecordPage {
+    /// Takes records until one of the limits is reached. The records past the limits are not
+    /// read.
+    pub(crate) fn from_records(
+        records: impl Iterator<Item = OwnedRecord>,
+        from: u64,
+        queue_next_position: u64,
+        max_records: usize,
+        max_bytes: usize,
+    ) -> RecordPage {
+        let mut page_records = Vec::new();
+        let mut num_bytes = 0;
+        let mut next_position = from;
+        for record in records.take(max_records) {
+            // The first record is returned even if it exceeds the byte limit, so that reading
+            // page after page always makes progress.
+            if !page_records.is_empty() && num_bytes + record.payload.len() > max_bytes {
+                break;
+            }
+            num_bytes += record.payload.len();
+            next_position = record.position + 1;
+            page_records.push(record);
+        }
+        // The first record is always taken: unless `max_records` is 0, an empty page means that no
+        // record follows `from`, which may come before the first record of the queue.
+        if page_records.is_empty() && max_records > 0 {
+            next_position = next_position.max(queue_next_position);
+        }
+        RecordPage {
+            records: page_records,
+            next_position,
+            end_reached: next_position >= queue_next_position,
+        }
+    }
+}
+
 /// Resources used by mrecordlog
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ResourceUsage {
     /// Actual size of the memory used
     pub memory_used_bytes: usize,
     /// Capacity allocated, a part of which may be unused right now
+    ///
+    /// Records returned by [`MultiRecordLog::range_owned`] or a [`LogReader`] keep the memory
+    /// holding their payload allocated even once truncated. That memory is not counted.
     pub memory_allocated_bytes: usize,
     /// Disk size used
     pub disk_used_bytes: usize,
 }
 
+/// Resources used by a single queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueResourceUsage {
+    /// Actual size of the memory used by the queue
+    pub memory_used_bytes: usize,
+    /// Capacity allocated for the queue, a part of which may be unused right now. Like
+    /// [`ResourceUsage::memory_allocated_bytes`], it ignores memory only kept allocated by
+    /// records handed out to readers.
+    pub memory_allocated_bytes: usize,
+    /// Number of records held in memory
+    pub num_records: usize,
+    /// Oldest wal file the queue prevents from being GCed, if any
+    pub oldest_pinned_file: Option<u64>,
+    /// Disk space that would be freed if the queue were truncated up to its last record, or
+    /// deleted. Files pinned by other queues as well are not counted.
+    pub reclaimable_disk_bytes: usize,
+}
+
+/// A queue preventing wal files from being GCed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GcBlockage {
+    /// Queue holding the oldest wal file
+    pub queue: String,
+    /// Oldest wal file, which cannot be GCed as long as the queue holds records in it
+    pub file_number: u64,
+    /// For how long the oldest wal file has been kept, while it could otherwise have been GCed:
+    /// since the log rolled past it, or since the files before it were GCed
+    pub pinned_for: std::time::Duration,
+    /// Number of wal files kept because of the queue, excluding the file being written
+    pub num_pinned_files: usize,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct AppendOutcome {
     /// Position of the last record appended, or `None` for an idempotent no-op
@@ -53,6 +160,8 @@ pub struct AppendOutcome {
     /// depending on where the write cursor sat within the current block (a frame crossing
     /// a block boundary incurs padding). `0` for an idempotent no-op.
     pub wal_bytes_written: u64,
+    /// Whether this append triggered a persist, according to the [`PersistPolicy`].
+    pub persisted: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -0,0 +1,62 @@
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::GcBlockage;
+
+/// Receives the events happening within a [`MultiRecordLog`](crate::MultiRecordLog).
+///
+/// Callbacks are called synchronously, from the thread performing the operation, right after
+/// it succeeded. They should be cheap, and must not call back into the log.
+///
+/// Every callback does nothing by default.
+pub trait LogListener: Send + Sync {
+    /// A queue was created.
+    fn on_queue_created(&self, _queue: &str) {}
+
+    /// A queue was deleted.
+    fn on_queue_deleted(&self, _queue: &str) {}
+
+    /// Records were appended to a queue. `wal_bytes_written` is `0` for volatile queues.
+    fn on_records_appended(
+        &self,
+        _queue: &str,
+        _positions: RangeInclusive<u64>,
+        _wal_bytes_written: u64,
+    ) {
+    }
+
+    /// A queue was truncated up to `position`, included.
+    fn on_truncated(
+        &self,
+        _queue: &str,
+        _position: u64,
+        _evicted_records: usize,
+        _wal_bytes_written: u64,
+    ) {
+    }
+
+    /// The writer rolled from a full wal file to the next one.
+    fn on_file_rolled(&self, _previous_file_number: u64, _file_number: u64) {}
+
+    /// A wal file was GCed. The file no longer exists at `filepath` when this is called.
+    fn on_file_gced(&self, _file_number: u64, _filepath: &Path) {}
+
+    /// The oldest wal file has been pinned by a queue for longer than the
+    /// `gc_blockage_threshold` option. This is called once per blocking queue and file.
+    fn on_gc_blocked(&self, _gc_blockage: &GcBlockage) {}
+
+    /// A corrupted record was skipped while reading the wal file `file_number` when opening
+    /// the log. Some data may have been lost.
+    fn on_corruption(&self, _file_number: u64) {}
+}
+
+impl std::fmt::Debug for dyn LogListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("LogListener")
+    }
+}
+
+/// Listener used when none is registered.
+pub(crate) struct NoopListener;
+
+impl LogListener for NoopListener {}
@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+
+use bytes::{Bytes, BytesMut};
+
+// Chunks grow from the minimum size to the maximum size as the queue receives data, so that
+// idle queues only hold small chunks.
+const MIN_CHUNK_NUM_BYTES: usize = 4 << 10;
+const MAX_CHUNK_NUM_BYTES: usize = 1 << 20;
+
+struct SealedChunk {
+    capacity: usize,
+    // Number of payloads of the chunk that were not released yet.
+    num_payloads: usize,
+    // Unwritten end of the chunk, which keeps a handle on its allocation so that it can be
+    // reused once all of its payloads are released.
+    tail: BytesMut,
+}
+
+/// Storage for the payloads of a queue, written one after the other into reference-counted
+/// chunks.
+///
+/// Pushing a payload copies it into the chunk being filled, and returns a view on it that can
+/// be cloned without copying. A payload never spans two chunks. Payloads are released in the
+/// order they were pushed, and a chunk is freed once all of its payloads are released and no
+/// view on them remains.
+///
+/// The last chunk whose payloads were all released is kept aside, and filled again instead of
+/// allocating a new chunk if no view on it remains by then. A queue that is appended to and
+/// truncated at the same pace therefore reuses the same chunks.
+#[derive(Default)]
+pub struct ChunkedBuffer {
+    sealed_chunks: VecDeque<SealedChunk>,
+    sealed_chunks_capacity: usize,
+    spare_chunk_opt: Option<SealedChunk>,
+    // Unwritten part of the chunk being filled.
+    active_chunk: BytesMut,
+    active_chunk_capacity: usize,
+    active_chunk_num_payloads: usize,
+    len: usize,
+}
+
+impl ChunkedBuffer {
+    /// Returns the number of bytes of the payloads not released yet.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the number of bytes allocated by the chunks holding payloads not released yet,
+    /// and by the chunk kept aside for reuse.
+    ///
+    /// Views on released payloads, such as the ones returned by readers, keep their chunk
+    /// allocated until they are dropped. These chunks are not counted.
+    pub fn capacity(&self) -> usize {
+        let spare_chunk_capacity = self
+            .spare_chunk_opt
+            .as_ref()
+            .map(|spare_chunk| spare_chunk.capacity)
+            .unwrap_or(0);
+        self.sealed_chunks_capacity + self.active_chunk_capacity + spare_chunk_capacity
+    }
+
+    /// Releases every payload, and the chunk being filled.
+    pub fn clear(&mut self) {
+        *self = ChunkedBuffer::default();
+    }
+
+    /// Releases the `num_payloads` oldest payloads, which sum up to `num_bytes`.
+    pub fn release_front(&mut self, mut num_payloads: usize, num_bytes: usize) {
+        self.len -= num_bytes;
+        while let Some(sealed_chunk) = self.sealed_chunks.front_mut() {
+            if sealed_chunk.num_payloads > num_payloads {
+                sealed_chunk.num_payloads -= num_payloads;
+                return;
+            }
+            num_payloads -= sealed_chunk.num_payloads;
+            self.sealed_chunks_capacity -= sealed_chunk.capacity;
+            self.spare_chunk_opt = self.sealed_chunks.pop_front();
+        }
+        // The chunk being filled is kept, even if all of its payloads are released, as the next
+        // payloads will be written to it.
+        self.active_chunk_num_payloads -= num_payloads;
+    }
+
+    /// Copies a payload at the end of the buffer.
+    pub fn push(&mut self, payload: &[u8]) -> Bytes {
+        if self.active_chunk.capacity() < payload.len() {
+            self.seal_active_chunk(payload.len());
+        }
+        // The chunk has enough capacity left, this does not reallocate.
+        self.active_chunk.extend_from_slice(payload);
+        self.active_chunk_num_payloads += 1;
+        self.len += payload.len();
+        self.active_chunk.split().freeze()
+    }
+
+    /// Starts a new chunk, large enough to hold at least `min_capacity` bytes.
+    fn seal_active_chunk(&mut self, min_capacity: usize) {
+        let chunk_capacity = (self.active_chunk_capacity * 2)
+            .clamp(MIN_CHUNK_NUM_BYTES, MAX_CHUNK_NUM_BYTES)
+            .max(min_capacity);
+        let active_chunk = std::mem::take(&mut self.active_chunk);
+        if self.active_chunk_num_payloads > 0 {
+            self.sealed_chunks.push_back(SealedChunk {
+                capacity: self.active_chunk_capacity,
+                num_payloads: self.active_chunk_num_payloads,
+                tail: active_chunk,
+            });
+            self.sealed_chunks_capacity += self.active_chunk_capacity;
+        }
+        self.active_chunk_num_payloads = 0;
+        // The spare chunk can only be filled again once no view on its payloads remains.
+        if let Some(mut spare_chunk) = self.spare_chunk_opt.take() {
+            if spare_chunk.capacity >= chunk_capacity
+                && spare_chunk.tail.try_reclaim(chunk_capacity)
+            {
+                self.active_chunk = spare_chunk.tail;
+                self.active_chunk_capacity = spare_chunk.capacity;
+                return;
+            }
+        }
+        self.active_chunk = BytesMut::with_capacity(chunk_capacity);
+        self.active_chunk_capacity = chunk_capacity;
+    }
+}
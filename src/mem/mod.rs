@@ -1,11 +1,11 @@
+mod chunked_buffer;
 mod queue;
 mod queues;
-mod rolling_buffer;
 mod summary;
 
 pub(crate) use self::queue::MemQueue;
 pub(crate) use self::queues::MemQueues;
-pub use self::summary::{QueueSummary, QueuesSummary};
+pub use self::summary::{GlobalSummary, QueueSummary, QueuesSummary};
 
 #[cfg(test)]
 mod tests;
@@ -1,14 +1,20 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
 use std::ops::{Bound, RangeBounds, RangeToInclusive};
+use std::time::SystemTime;
 
-use super::rolling_buffer::RollingBuffer;
+use bytes::Bytes;
+
+use super::chunked_buffer::ChunkedBuffer;
 use crate::error::AppendError;
 use crate::mem::QueueSummary;
 use crate::rolling::FileNumber;
-use crate::Record;
+use crate::{DurabilityClass, OwnedRecord, Record, RecordPage};
 
 #[derive(Clone)]
 struct RecordMeta {
-    start_offset: usize,
+    // View on the chunk of `payloads` holding the payload.
+    payload: Bytes,
     // in a vec of RecordMeta, this field should be set only on the last record
     // which relate to that File.
     file_number: Option<FileNumber>,
@@ -17,18 +23,29 @@ struct RecordMeta {
 
 #[derive(Default)]
 pub(crate) struct MemQueue {
-    // Concatenated records
-    concatenated_records: RollingBuffer,
+    payloads: ChunkedBuffer,
     start_position: u64,
     record_metas: Vec<RecordMeta>,
+    // Number of records in each wal file, as `(file_number, num_records)`, oldest first.
+    // Volatile records are not counted.
+    num_records_per_file: VecDeque<(u64, usize)>,
+    durability_class: DurabilityClass,
+    // Times of the last append and truncation since the log was opened. Replaying the wal does
+    // not update them.
+    last_append_time: Option<SystemTime>,
+    last_truncate_time: Option<SystemTime>,
 }
 
 impl MemQueue {
     pub fn with_next_position(next_position: u64) -> Self {
         MemQueue {
-            concatenated_records: RollingBuffer::new(),
+            payloads: ChunkedBuffer::default(),
             start_position: next_position,
             record_metas: Vec::new(),
+            num_records_per_file: VecDeque::new(),
+            durability_class: DurabilityClass::default(),
+            last_append_time: None,
+            last_truncate_time: None,
         }
     }
 
@@ -37,20 +54,48 @@ impl MemQueue {
             start: self.start_position(),
             end: self.last_position(),
             file_number: self.first_file_number(),
+            num_records: self.num_records(),
+            payload_bytes: self.payloads.len(),
+            memory_capacity_bytes: self.capacity(),
+            num_files: self.num_files(),
+            durability_class: self.durability_class,
+            last_append_time: self.last_append_time,
+            last_truncate_time: self.last_truncate_time,
         }
     }
 
+    pub fn touch_append(&mut self) {
+        self.last_append_time = Some(SystemTime::now());
+    }
+
+    pub fn touch_truncate(&mut self) {
+        self.last_truncate_time = Some(SystemTime::now());
+    }
+
+    pub fn durability_class(&self) -> DurabilityClass {
+        self.durability_class
+    }
+
+    pub fn set_durability_class(&mut self, durability_class: DurabilityClass) {
+        self.durability_class = durability_class;
+    }
+
+    pub fn num_records(&self) -> usize {
+        self.record_metas.len()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.record_metas.is_empty()
     }
 
     pub(crate) fn first_file_number(&self) -> Option<u64> {
-        let file_number: &FileNumber = self
-            .record_metas
-            .iter()
-            .filter_map(|record_meta| record_meta.file_number.as_ref())
-            .next()?;
-        Some(file_number.file_number())
+        let (file_number, _) = self.num_records_per_file.front()?;
+        Some(*file_number)
+    }
+
+    /// Returns the number of wal files holding records of this queue.
+    fn num_files(&self) -> usize {
+        self.num_records_per_file.len()
     }
 
     pub(crate) fn start_position(&self) -> u64 {
@@ -66,7 +111,7 @@ impl MemQueue {
     pub fn last_record(&self) -> Option<Record<'_>> {
         self.record_metas.last().map(|record| Record {
             position: record.position,
-            payload: self.concatenated_records.get_range(record.start_offset..),
+            payload: Cow::Borrowed(&record.payload[..]),
         })
     }
 
@@ -87,6 +132,24 @@ impl MemQueue {
         file_number: &FileNumber,
         target_position: u64,
         payload: &[u8],
+    ) -> Result<(), AppendError> {
+        self.append_record_opt_file(Some(file_number), target_position, payload)
+    }
+
+    /// Appends a new record that is not stored in any file, for volatile queues.
+    pub fn append_volatile_record(
+        &mut self,
+        target_position: u64,
+        payload: &[u8],
+    ) -> Result<(), AppendError> {
+        self.append_record_opt_file(None, target_position, payload)
+    }
+
+    fn append_record_opt_file(
+        &mut self,
+        file_number_opt: Option<&FileNumber>,
+        target_position: u64,
+        payload: &[u8],
     ) -> Result<(), AppendError> {
         let next_position = self.next_position();
         if target_position < next_position {
@@ -97,23 +160,33 @@ impl MemQueue {
             self.start_position = target_position;
         }
 
-        let file_number = if let Some(record_meta) = self.record_metas.last_mut() {
-            if record_meta.file_number.as_ref() == Some(file_number) {
-                record_meta.file_number.take().unwrap()
-            } else {
-                file_number.clone()
+        if let Some(file_number) = file_number_opt {
+            match self.num_records_per_file.back_mut() {
+                Some((last_file_number, num_records))
+                    if *last_file_number == file_number.file_number() =>
+                {
+                    *num_records += 1;
+                }
+                _ => self
+                    .num_records_per_file
+                    .push_back((file_number.file_number(), 1)),
+            }
+        }
+        let file_number = file_number_opt.map(|file_number| {
+            if let Some(record_meta) = self.record_metas.last_mut() {
+                if record_meta.file_number.as_ref() == Some(file_number) {
+                    return record_meta.file_number.take().unwrap();
+                }
             }
-        } else {
             file_number.clone()
-        };
+        });
 
         let record_meta = RecordMeta {
-            start_offset: self.concatenated_records.len(),
-            file_number: Some(file_number),
+            payload: self.payloads.push(payload),
+            file_number,
             position: target_position,
         };
         self.record_metas.push(record_meta);
-        self.concatenated_records.extend(payload);
         Ok(())
     }
 
@@ -126,7 +199,7 @@ impl MemQueue {
             .binary_search_by_key(&position, |record| record.position)
     }
 
-    pub fn range<R>(&self, range: R) -> impl Iterator<Item = Record<'_>> + '_
+    fn range_metas<R>(&self, range: R) -> impl Iterator<Item = &RecordMeta> + '_
     where R: RangeBounds<u64> + 'static {
         let start_idx: usize = match range.start_bound() {
             Bound::Included(&start_from) => {
@@ -143,21 +216,35 @@ impl MemQueue {
             }
             Bound::Unbounded => 0,
         };
-        (start_idx..self.record_metas.len())
-            .take_while(move |idx| range.contains(&self.record_metas[*idx].position))
-            .map(move |idx| {
-                let record = &self.record_metas[idx];
-                let position = record.position;
-                let start_offset = record.start_offset;
-                let payload = if let Some(next_record_meta) = self.record_metas.get(idx + 1) {
-                    let end_offset = next_record_meta.start_offset;
-                    self.concatenated_records
-                        .get_range(start_offset..end_offset)
-                } else {
-                    self.concatenated_records.get_range(start_offset..)
-                };
-                Record { position, payload }
-            })
+        self.record_metas[start_idx..]
+            .iter()
+            .take_while(move |record| range.contains(&record.position))
+    }
+
+    pub fn range<R>(&self, range: R) -> impl Iterator<Item = Record<'_>> + '_
+    where R: RangeBounds<u64> + 'static {
+        self.range_metas(range).map(|record| Record {
+            position: record.position,
+            payload: Cow::Borrowed(&record.payload[..]),
+        })
+    }
+
+    pub fn range_owned<R>(&self, range: R) -> impl Iterator<Item = OwnedRecord> + '_
+    where R: RangeBounds<u64> + 'static {
+        self.range_metas(range).map(|record| OwnedRecord {
+            position: record.position,
+            payload: record.payload.clone(),
+        })
+    }
+
+    pub fn range_with_limits(&self, from: u64, max_records: usize, max_bytes: usize) -> RecordPage {
+        RecordPage::from_records(
+            self.range_owned(from..),
+            from,
+            self.next_position(),
+            max_records,
+            max_bytes,
+        )
     }
 
     /// Removes all records coming before position, and including the record at "position".
@@ -171,7 +258,8 @@ impl MemQueue {
         }
         if truncate_up_to_pos + 1 >= self.next_position() {
             self.start_position = truncate_up_to_pos + 1;
-            self.concatenated_records.clear();
+            self.payloads.clear();
+            self.num_records_per_file.clear();
             let record_count = self.record_metas.len();
             self.record_metas.clear();
             return record_count;
@@ -180,24 +268,31 @@ impl MemQueue {
             .position_to_idx(truncate_up_to_pos + 1)
             .unwrap_or_else(std::convert::identity);
 
-        let start_offset_to_keep: usize = self.record_metas[first_record_to_keep].start_offset;
+        let num_bytes_to_release: usize = self.record_metas[..first_record_to_keep]
+            .iter()
+            .map(|record_meta| record_meta.payload.len())
+            .sum();
         self.record_metas.drain(..first_record_to_keep);
-        for record_meta in &mut self.record_metas {
-            record_meta.start_offset -= start_offset_to_keep;
+        self.payloads
+            .release_front(first_record_to_keep, num_bytes_to_release);
+        let mut num_records_to_release = first_record_to_keep;
+        while let Some((_, num_records)) = self.num_records_per_file.front_mut() {
+            if *num_records > num_records_to_release {
+                *num_records -= num_records_to_release;
+                break;
+            }
+            num_records_to_release -= *num_records;
+            self.num_records_per_file.pop_front();
         }
-        self.concatenated_records
-            .truncate_head(..start_offset_to_keep);
         self.start_position = truncate_up_to_pos + 1;
         first_record_to_keep
     }
 
     pub fn size(&self) -> usize {
-        self.concatenated_records.len()
-            + self.record_metas.len() * std::mem::size_of::<RecordMeta>()
+        self.payloads.len() + self.record_metas.len() * std::mem::size_of::<RecordMeta>()
     }
 
     pub fn capacity(&self) -> usize {
-        self.concatenated_records.capacity()
-            + self.record_metas.capacity() * std::mem::size_of::<RecordMeta>()
+        self.payloads.capacity() + self.record_metas.capacity() * std::mem::size_of::<RecordMeta>()
     }
 }
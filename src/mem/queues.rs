@@ -6,7 +6,7 @@ use tracing::{info, warn};
 use crate::error::{AlreadyExists, AppendError, MissingQueue};
 use crate::mem::{MemQueue, QueuesSummary};
 use crate::rolling::FileNumber;
-use crate::Record;
+use crate::{DurabilityClass, OwnedRecord, Record, RecordPage};
 
 #[derive(Default)]
 pub(crate) struct MemQueues {
@@ -15,11 +15,22 @@ pub(crate) struct MemQueues {
 impl MemQueues {
     /// The file number argument is here unused. Its point is just to make sure we
     /// flushed the file before updating the in memory queue.
+    #[cfg(test)]
     pub fn create_queue(&mut self, queue: &str) -> Result<(), AlreadyExists> {
+        self.create_queue_with_class(queue, DurabilityClass::default())
+    }
+
+    pub fn create_queue_with_class(
+        &mut self,
+        queue: &str,
+        durability_class: DurabilityClass,
+    ) -> Result<(), AlreadyExists> {
         if self.queues.contains_key(queue) {
             return Err(AlreadyExists);
         }
-        self.queues.insert(queue.to_string(), MemQueue::default());
+        let mut mem_queue = MemQueue::default();
+        mem_queue.set_durability_class(durability_class);
+        self.queues.insert(queue.to_string(), mem_queue);
         Ok(())
     }
 
@@ -66,6 +77,29 @@ impl MemQueues {
         }
     }
 
+    pub fn range_owned<R>(
+        &self,
+        queue: &str,
+        range: R,
+    ) -> Result<impl Iterator<Item = OwnedRecord> + '_, MissingQueue>
+    where
+        R: RangeBounds<u64> + 'static,
+    {
+        Ok(self.get_queue(queue)?.range_owned(range))
+    }
+
+    pub fn range_with_limits(
+        &self,
+        queue: &str,
+        from: u64,
+        max_records: usize,
+        max_bytes: usize,
+    ) -> Result<RecordPage, MissingQueue> {
+        Ok(self
+            .get_queue(queue)?
+            .range_with_limits(from, max_records, max_bytes))
+    }
+
     pub(crate) fn get_queue(&self, queue: &str) -> Result<&MemQueue, MissingQueue> {
         // We do not rely on `entry` in order to avoid
         // the allocation.
@@ -93,6 +127,34 @@ impl MemQueues {
             .append_record(file_number, target_position, payload)
     }
 
+    pub fn set_durability_class(
+        &mut self,
+        queue: &str,
+        durability_class: DurabilityClass,
+    ) -> Result<(), MissingQueue> {
+        self.get_queue_mut(queue)?
+            .set_durability_class(durability_class);
+        Ok(())
+    }
+
+    pub fn durability_class(&self, queue: &str) -> Result<DurabilityClass, MissingQueue> {
+        Ok(self.get_queue(queue)?.durability_class())
+    }
+
+    /// Returns the queues that don't have the default durability class.
+    pub fn non_default_durability_classes(
+        &self,
+    ) -> impl Iterator<Item = (&str, DurabilityClass)> + '_ {
+        self.queues.iter().filter_map(|(queue, mem_queue)| {
+            let durability_class = mem_queue.durability_class();
+            if durability_class == DurabilityClass::default() {
+                None
+            } else {
+                Some((queue.as_str(), durability_class))
+            }
+        })
+    }
+
     pub fn contains_queue(&self, queue: &str) -> bool {
         self.queues.contains_key(queue)
     }
@@ -162,6 +224,13 @@ impl MemQueues {
         }
     }
 
+    /// Iterates over the queues and their name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &MemQueue)> + '_ {
+        self.queues
+            .iter()
+            .map(|(queue, mem_queue)| (queue.as_str(), mem_queue))
+    }
+
     /// Return a tuple of (size, capacity) of memory used by the memqueues
     pub fn size(&self) -> (usize, usize) {
         let size = self
@@ -1,15 +1,53 @@
 use std::collections::BTreeMap;
+use std::time::SystemTime;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Default, Serialize, Debug)]
+use crate::{DurabilityClass, PersistPolicy};
+
+#[derive(Default, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct QueueSummary {
     pub start: u64,
     pub end: Option<u64>,
     pub file_number: Option<u64>,
+    // The following fields are missing from summaries serialized by older versions.
+    /// Number of records held in memory.
+    #[serde(default)]
+    pub num_records: usize,
+    /// Sum of the sizes of the payloads held in memory.
+    #[serde(default)]
+    pub payload_bytes: usize,
+    /// Memory allocated by the queue, including unused capacity. Truncated records still
+    /// referenced by readers are not counted.
+    #[serde(default)]
+    pub memory_capacity_bytes: usize,
+    /// Number of wal files holding the records of the queue.
+    #[serde(default)]
+    pub num_files: usize,
+    #[serde(default)]
+    pub durability_class: DurabilityClass,
+    /// Time of the last append since the log was opened.
+    #[serde(default)]
+    pub last_append_time: Option<SystemTime>,
+    /// Time of the last truncation since the log was opened.
+    #[serde(default)]
+    pub last_truncate_time: Option<SystemTime>,
+}
+
+/// State of the log as a whole.
+#[derive(Default, Serialize, Deserialize, Debug, Clone)]
+pub struct GlobalSummary {
+    /// Number of wal files on disk.
+    pub num_files: usize,
+    /// Number of the wal file currently written to.
+    pub current_file_number: u64,
+    pub persist_policy: PersistPolicy,
 }
 
-#[derive(Default, Serialize)]
+#[derive(Default, Serialize, Deserialize, Debug, Clone)]
 pub struct QueuesSummary {
+    // Missing from summaries serialized by older versions.
+    #[serde(default)]
+    pub global: GlobalSummary,
     pub queues: BTreeMap<String, QueueSummary>,
 }
@@ -239,3 +239,55 @@ fn test_mem_queues_keep_filenum() {
 
     assert!(files[2].can_be_deleted());
 }
+
+#[test]
+fn test_chunked_buffer() {
+    let mut buffer = chunked_buffer::ChunkedBuffer::default();
+    assert_eq!(buffer.capacity(), 0);
+    let hello = buffer.push(b"hello");
+    let world = buffer.push(b"world");
+    assert_eq!(&hello[..], b"hello");
+    assert_eq!(&world[..], b"world");
+    // Both payloads share the same chunk.
+    assert_eq!(hello.as_ptr().wrapping_add(5), world.as_ptr());
+    assert_eq!(buffer.len(), 10);
+    let first_chunk_capacity = buffer.capacity();
+    // Does not fit in the first chunk.
+    let large = buffer.push(&vec![1u8; first_chunk_capacity][..]);
+    assert_eq!(large.len(), first_chunk_capacity);
+    assert!(buffer.capacity() >= 2 * first_chunk_capacity);
+    // Releasing a part of a chunk keeps it.
+    buffer.release_front(1, 5);
+    assert_eq!(buffer.len(), first_chunk_capacity + 5);
+    assert!(buffer.capacity() >= 2 * first_chunk_capacity);
+    // The released chunk is kept aside for reuse.
+    buffer.release_front(1, 5);
+    assert_eq!(buffer.capacity(), first_chunk_capacity * 3);
+    // The views outlive the release of their payloads.
+    assert_eq!(&world[..], b"world");
+    buffer.clear();
+    assert_eq!(buffer.len(), 0);
+    assert_eq!(buffer.capacity(), 0);
+}
+
+#[test]
+fn test_chunked_buffer_reuses_released_chunk() {
+    let mut buffer = chunked_buffer::ChunkedBuffer::default();
+    let payload = vec![1u8; 1 << 20];
+    let first = buffer.push(&payload[..]);
+    let first_ptr = first.as_ptr();
+    let second = buffer.push(&payload[..]);
+    let second_ptr = second.as_ptr();
+    drop(first);
+    buffer.release_front(1, payload.len());
+    // The released chunk is kept aside.
+    assert_eq!(buffer.capacity(), 2 * payload.len());
+    let third = buffer.push(&payload[..]);
+    assert_eq!(third.as_ptr(), first_ptr);
+    assert_eq!(buffer.capacity(), 2 * payload.len());
+    // A chunk still viewed is not reused.
+    buffer.release_front(1, payload.len());
+    let fourth = buffer.push(&payload[..]);
+    assert_ne!(fourth.as_ptr(), second_ptr);
+    assert_eq!(&second[..], &payload[..]);
+}
@@ -1,54 +1,122 @@
+use std::collections::{BTreeMap, HashMap};
 use std::io;
 use std::ops::{RangeBounds, RangeToInclusive};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 
 use bytes::Buf;
 use tracing::{debug, event_enabled, info, warn, Level};
 
+use crate::background_persist::{
+    lock_writer, persist_and_track_durability, BackgroundPersister, SharedWriter,
+};
+use crate::durability::DurabilityWatcher;
 use crate::error::{
-    AppendError, CreateQueueError, DeleteQueueError, MissingQueue, ReadRecordError, TruncateError,
+    is_disk_full, AppendError, CreateQueueError, DeleteQueueError, MissingQueue, ReadRecordError,
+    TruncateError,
 };
-use crate::mem::{MemQueue, QueuesSummary};
+use crate::listener::{LogListener, NoopListener};
+use crate::mem::{GlobalSummary, MemQueue, QueuesSummary};
+use crate::reader::LogReader;
 use crate::record::{MultiPlexedRecord, MultiRecord};
 use crate::recordlog::RecordWriter;
 use crate::rolling::RollingWriter;
+use crate::stats::IoStatsRecorder;
 use crate::{
-    mem, AppendOutcome, CreateQueueOutcome, DeleteQueueOutcome, PersistAction, PersistPolicy,
-    PersistState, Record, ResourceUsage, TruncateOutcome,
+    mem, AppendOutcome, CreateQueueOutcome, DeleteQueueOutcome, DurabilityClass, GcBlockage,
+    IoStats, MultiRecordLogOptions, OwnedRecord, PersistAction, PersistPolicy, PersistState,
+    QueueResourceUsage, Record, RecordPage, ResourceUsage, TruncateOutcome,
 };
 
 pub struct MultiRecordLog {
-    record_log_writer: crate::recordlog::RecordWriter<RollingWriter>,
+    record_log_writer: SharedWriter,
+    // Persists the shared writer at a fixed interval. Dropping it stops its thread.
+    _background_persister: Option<BackgroundPersister>,
+    durability_watcher: DurabilityWatcher,
+    // Created by the first call to `reader`, so that logs without readers don't pay for them.
+    reader_opt: Option<LogReader>,
+    // Number of wal files and number of the current file, as last published to the reader.
+    published_wal_files: Option<(usize, u64)>,
+    io_stats: Arc<IoStatsRecorder>,
+    listener: Arc<dyn LogListener>,
+    gc_blockage_threshold: Option<Duration>,
+    // Queue and file of the last reported gc blockage, to report it only once.
+    reported_gc_blockage: Option<(String, u64)>,
     in_mem_queues: mem::MemQueues,
+    persist_policy: PersistPolicy,
     next_persist: PersistState,
+    // Set when the disk got full. While degraded, the log refuses appends, queue creations and
+    // deletions until the WAL writer could be recovered.
+    degraded: bool,
+    // Set by the background persister when the disk got full.
+    background_disk_full: Arc<AtomicBool>,
+    // Truncations applied in memory while degraded, which still need to be written to the WAL.
+    pending_truncates: HashMap<String, u64>,
     // A simple buffer we reuse to avoid allocation.
     multi_record_spare_buffer: Vec<u8>,
 }
 
+/// Returns the number of wal files, and the number of the current file.
+fn wal_files(writer: &RecordWriter<RollingWriter>) -> (usize, u64) {
+    let rolling_writer = writer.get_underlying_wrt();
+    (
+        rolling_writer.num_files(),
+        rolling_writer.current_file().file_number(),
+    )
+}
+
 impl MultiRecordLog {
     /// Open the multi record log, flushing after each operation, but not fsyncing.
     pub fn open(directory_path: &Path) -> Result<Self, ReadRecordError> {
-        Self::open_with_prefs(directory_path, PersistPolicy::Always(PersistAction::Flush))
+        Self::open_with_options(directory_path, MultiRecordLogOptions::default())
     }
 
     pub fn summary(&self) -> QueuesSummary {
-        self.in_mem_queues.summary()
+        let mut summary = self.in_mem_queues.summary();
+        summary.global = self.global_summary();
+        summary
+    }
+
+    fn global_summary(&self) -> GlobalSummary {
+        let (num_files, current_file_number) = wal_files(&self.writer());
+        GlobalSummary {
+            num_files,
+            current_file_number,
+            persist_policy: self.persist_policy.clone(),
+        }
     }
 
     /// Open the multi record log, syncing following the provided policy.
     pub fn open_with_prefs(
         directory_path: &Path,
         persist_policy: PersistPolicy,
+    ) -> Result<Self, ReadRecordError> {
+        let options = MultiRecordLogOptions {
+            persist_policy,
+            ..Default::default()
+        };
+        Self::open_with_options(directory_path, options)
+    }
+
+    /// Open the multi record log with the provided options.
+    pub fn open_with_options(
+        directory_path: &Path,
+        options: MultiRecordLogOptions,
     ) -> Result<Self, ReadRecordError> {
         // io errors are non-recoverable
         let rolling_reader = crate::rolling::RollingReader::open(directory_path)?;
         let mut record_reader = crate::recordlog::RecordReader::open(rolling_reader);
         let mut in_mem_queues = crate::mem::MemQueues::default();
+        let listener: Arc<dyn LogListener> =
+            options.listener.unwrap_or_else(|| Arc::new(NoopListener));
         debug!("loading wal");
         loop {
             let file_number = record_reader.read().current_file().clone();
             let Ok(record) = record_reader.read_record::<MultiPlexedRecord>() else {
                 warn!("Detected corrupted record: some data may have been lost");
+                listener.on_corruption(file_number.file_number());
                 continue;
             };
             if let Some(record) = record {
@@ -84,6 +152,11 @@ impl MultiRecordLog {
                     MultiPlexedRecord::RecordPosition { queue, position } => {
                         in_mem_queues.ack_position(queue, position);
                     }
+                    MultiPlexedRecord::DurabilityClass { queue, class } => {
+                        // can fail if we don't know about the queue, in which case there is
+                        // nothing to apply the class to.
+                        let _ = in_mem_queues.set_durability_class(queue, class);
+                    }
                     MultiPlexedRecord::DeleteQueue { queue, position: _ } => {
                         // can fail if we don't know about the queue getting deleted. It's fine to
                         // just ignore the error, the queue no longer exists either way.
@@ -95,49 +168,225 @@ impl MultiRecordLog {
             }
         }
         // io errors are non-recoverable
-        let record_log_writer: RecordWriter<RollingWriter> = record_reader.into_writer()?;
+        let mut record_log_writer: RecordWriter<RollingWriter> = record_reader.into_writer()?;
+        record_log_writer
+            .directory()
+            .set_gc_action(options.gc_action);
+        record_log_writer.directory().set_listener(listener.clone());
+        let io_stats = record_log_writer.directory().stats.clone();
+        let record_log_writer: SharedWriter = Arc::new(Mutex::new(record_log_writer));
+        // Replayed records may only have reached the page cache if the previous writer crashed.
+        // They are durable now: the files it rolled from were fsynced when it rolled, and
+        // `into_writer` fsyncs the last one.
+        let durability_watcher = DurabilityWatcher::default();
+        for queue in in_mem_queues.list_queues() {
+            if let Ok(Some(last_position)) = in_mem_queues.last_position(queue) {
+                durability_watcher.set_durable(queue, last_position);
+            }
+        }
+        let background_disk_full = Arc::new(AtomicBool::new(false));
+        let background_persister = if options.background_persist {
+            options
+                .persist_policy
+                .persist_interval()
+                .map(|(interval, persist_action)| {
+                    BackgroundPersister::spawn(
+                        record_log_writer.clone(),
+                        durability_watcher.clone(),
+                        interval,
+                        persist_action,
+                        background_disk_full.clone(),
+                    )
+                })
+                .transpose()?
+        } else {
+            None
+        };
+        let mut next_persist: PersistState = options.persist_policy.clone().into();
+        if background_persister.is_some() {
+            // Appends would otherwise persist again what the background thread persists.
+            next_persist = next_persist.without_delay();
+        }
         let mut multi_record_log = MultiRecordLog {
             record_log_writer,
+            _background_persister: background_persister,
+            durability_watcher,
+            reader_opt: None,
+            published_wal_files: None,
+            io_stats,
+            listener,
+            gc_blockage_threshold: options.gc_blockage_threshold,
+            reported_gc_blockage: None,
             in_mem_queues,
-            next_persist: persist_policy.into(),
+            next_persist,
+            persist_policy: options.persist_policy,
+            degraded: false,
+            background_disk_full,
+            pending_truncates: HashMap::new(),
             multi_record_spare_buffer: Vec::new(),
         };
         // Bytes written by recovery-time GC are not surfaced to any user-facing API.
         let _ = multi_record_log.run_gc_if_necessary()?;
+        multi_record_log.check_gc_blockage();
         Ok(multi_record_log)
     }
 
     #[cfg(test)]
     pub fn list_file_numbers(&self) -> Vec<u64> {
-        let rolling_writer = self.record_log_writer.get_underlying_wrt();
-        rolling_writer.list_file_numbers()
+        self.writer().get_underlying_wrt().list_file_numbers()
+    }
+
+    #[cfg(test)]
+    pub fn simulate_disk_full(&mut self, disk_full: bool) {
+        self.writer()
+            .simulate_disk_full(if disk_full { Some(0) } else { None });
+    }
+
+    /// Makes the disk full once `num_bytes_available` more bytes were written to the WAL.
+    #[cfg(test)]
+    pub fn simulate_disk_full_after(&mut self, num_bytes_available: usize) {
+        self.writer().simulate_disk_full(Some(num_bytes_available));
+    }
+
+    /// Returns true if the log is in read-only degraded mode, following a disk full error.
+    ///
+    /// In that mode, reads and truncations keep working, but appends, queue creations and
+    /// deletions fail with a `DiskFull` error. Each of these operations first attempts to
+    /// recover, so the log leaves that mode on its own once space has been freed.
+    pub fn is_degraded(&self) -> bool {
+        self.degraded || self.background_disk_full.load(Ordering::Relaxed)
+    }
+
+    fn writer(&self) -> MutexGuard<'_, RecordWriter<RollingWriter>> {
+        lock_writer(&self.record_log_writer)
+    }
+
+    /// Writes a record to the WAL, switching to degraded mode if the disk is full.
+    fn write_record(&mut self, record: MultiPlexedRecord) -> io::Result<u64> {
+        let mut writer = self.writer();
+        let write_res = writer.write_record(record);
+        // Writing may roll to a new file.
+        let wal_files = wal_files(&writer);
+        drop(writer);
+        self.publish_wal_files(wal_files);
+        self.degrade_on_disk_full(write_res)
+    }
+
+    fn degrade_on_disk_full<T>(&mut self, io_res: io::Result<T>) -> io::Result<T> {
+        if let Err(io_err) = &io_res {
+            if is_disk_full(io_err) && !self.degraded {
+                warn!("disk full: switching to read-only mode");
+                self.degraded = true;
+            }
+        }
+        io_res
+    }
+
+    /// Attempts to leave degraded mode.
+    ///
+    /// The WAL writer is brought back to a consistent state, the truncations that could not be
+    /// written while the disk was full are written, and GC runs.
+    fn recover_if_degraded(&mut self) -> io::Result<()> {
+        if self.background_disk_full.swap(false, Ordering::Relaxed) && !self.degraded {
+            warn!("disk full: switching to read-only mode");
+            self.degraded = true;
+        }
+        if !self.degraded {
+            return Ok(());
+        }
+        self.writer().recover_from_io_error()?;
+        let mut pending_truncates: Vec<(String, u64)> = self.pending_truncates.drain().collect();
+        while let Some((queue, position)) = pending_truncates.pop() {
+            if !self.queue_exists(&queue) {
+                continue;
+            }
+            let record = MultiPlexedRecord::Truncate {
+                queue: &queue,
+                truncate_range: ..=position,
+            };
+            let write_res = self.writer().write_record(record);
+            if let Err(io_err) = write_res {
+                self.pending_truncates.insert(queue, position);
+                self.pending_truncates.extend(pending_truncates);
+                return Err(io_err);
+            }
+        }
+        let wal_files = wal_files(&self.writer());
+        self.publish_wal_files(wal_files);
+        self.degraded = false;
+        info!("disk space available again: leaving read-only mode");
+        self.run_gc_if_necessary()?;
+        self.persist(PersistAction::Flush)
     }
 
     /// Creates a new queue.
     ///
     /// Returns an error if the queue already exists.
     pub fn create_queue(&mut self, queue: &str) -> Result<CreateQueueOutcome, CreateQueueError> {
-        info!(queue = queue, "create queue");
+        self.create_queue_with_class(queue, DurabilityClass::default())
+    }
+
+    /// Creates a new queue, whose appends are persisted according to the given durability
+    /// class.
+    ///
+    /// Volatile queues are not recorded in the WAL: they don't survive reopening the log.
+    pub fn create_queue_with_class(
+        &mut self,
+        queue: &str,
+        durability_class: DurabilityClass,
+    ) -> Result<CreateQueueOutcome, CreateQueueError> {
+        info!(queue = queue, durability_class=?durability_class, "create queue");
         if self.queue_exists(queue) {
             return Err(CreateQueueError::AlreadyExists);
         }
-        let record = MultiPlexedRecord::RecordPosition { queue, position: 0 };
-        let num_bytes_written = self.record_log_writer.write_record(record)?;
-        self.persist(PersistAction::FlushAndFsync)?;
-        self.in_mem_queues.create_queue(queue)?;
+        let mut num_bytes_written = 0;
+        if durability_class != DurabilityClass::Volatile {
+            self.recover_if_degraded()?;
+            let record = MultiPlexedRecord::RecordPosition { queue, position: 0 };
+            num_bytes_written += self.write_record(record)?;
+            if durability_class != DurabilityClass::default() {
+                let record = MultiPlexedRecord::DurabilityClass {
+                    queue,
+                    class: durability_class,
+                };
+                num_bytes_written += self.write_record(record)?;
+            }
+            self.persist(PersistAction::FlushAndFsync)?;
+        }
+        self.in_mem_queues
+            .create_queue_with_class(queue, durability_class)?;
+        self.publish_to_reader(queue);
+        self.listener.on_queue_created(queue);
         Ok(CreateQueueOutcome {
             wal_bytes_written: num_bytes_written,
         })
     }
 
+    /// Returns the durability class of the queue.
+    pub fn durability_class(&self, queue: &str) -> Result<DurabilityClass, MissingQueue> {
+        self.in_mem_queues.durability_class(queue)
+    }
+
     pub fn delete_queue(&mut self, queue: &str) -> Result<DeleteQueueOutcome, DeleteQueueError> {
         info!(queue = queue, "delete queue");
         let position = self.in_mem_queues.next_position(queue)?;
+        if self.in_mem_queues.durability_class(queue)? == DurabilityClass::Volatile {
+            self.in_mem_queues.delete_queue(queue)?;
+            self.publish_to_reader(queue);
+            self.listener.on_queue_deleted(queue);
+            return Ok(DeleteQueueOutcome {
+                wal_bytes_written: 0,
+            });
+        }
+        self.recover_if_degraded()?;
         let record = MultiPlexedRecord::DeleteQueue { queue, position };
-        let mut num_bytes_written = self.record_log_writer.write_record(record)?;
+        let mut num_bytes_written = self.write_record(record)?;
         self.in_mem_queues.delete_queue(queue)?;
+        self.durability_watcher.remove_queue(queue);
+        self.publish_to_reader(queue);
         num_bytes_written += self.run_gc_if_necessary()?;
         self.persist(PersistAction::FlushAndFsync)?;
+        self.listener.on_queue_deleted(queue);
         Ok(DeleteQueueOutcome {
             wal_bytes_written: num_bytes_written,
         })
@@ -154,8 +403,6 @@ impl MultiRecordLog {
     /// Appends a record to the log.
     ///
     /// The local_position argument can optionally be passed to enforce idempotence.
-    /// TODO if an io Error is encounterred, the in mem queue and the record log will
-    /// be in an inconsistent state.
     pub fn append_record(
         &mut self,
         queue: &str,
@@ -171,6 +418,12 @@ impl MultiRecordLog {
     /// However this function succeeding does not necessarily means records where stored, be sure
     /// to call [`Self::persist`] to make sure changes are persisted if you don't use
     /// [`PersistPolicy::Always`] (which is the default).
+    ///
+    /// If the records were written to the WAL but persisting them failed, they are still
+    /// appended to the in-memory queue, and the error is returned. Retrying such an append with
+    /// `position_opt` set to `None` appends the records a second time. Retries should pass the
+    /// position of the first record explicitly: if the records were appended, the retry is then
+    /// a no-op for a single record, and fails with [`AppendError::Past`] for several records.
     pub fn append_records<T: Iterator<Item = impl Buf>>(
         &mut self,
         queue: &str,
@@ -178,19 +431,30 @@ impl MultiRecordLog {
         payloads: T,
     ) -> Result<AppendOutcome, AppendError> {
         let next_position = self.in_mem_queues.next_position(queue)?;
+        let durability_class = self.in_mem_queues.durability_class(queue)?;
+        let is_volatile = durability_class == DurabilityClass::Volatile;
+        if !is_volatile {
+            self.recover_if_degraded()?;
+        }
         if let Some(position) = position_opt {
             // we accept position in the future, and move forward as required.
             if position + 1 == next_position {
                 return Ok(AppendOutcome {
                     last_position: None,
                     wal_bytes_written: 0,
+                    persisted: false,
                 });
             } else if position < next_position {
                 return Err(AppendError::Past);
             }
         }
         let position = position_opt.unwrap_or(next_position);
-        let file_number = self.record_log_writer.current_file().clone();
+        // Volatile records are not stored in any file.
+        let file_number_opt = if is_volatile {
+            None
+        } else {
+            Some(self.writer().current_file().clone())
+        };
 
         let mut multi_record_spare_buffer = std::mem::take(&mut self.multi_record_spare_buffer);
         MultiRecord::serialize(payloads, position, &mut multi_record_spare_buffer);
@@ -200,44 +464,88 @@ impl MultiRecordLog {
             return Ok(AppendOutcome {
                 last_position: None,
                 wal_bytes_written: 0,
+                persisted: false,
             });
         }
 
         let records = MultiRecord::new_unchecked(&multi_record_spare_buffer);
-        let record = MultiPlexedRecord::AppendRecords {
-            position,
-            queue,
-            records,
+        let last_position = records
+            .last()
+            .and_then(Result::ok)
+            .map(|(position, _)| position)
+            .unwrap_or(position);
+        let (num_bytes_written, persist_res) = if is_volatile {
+            (0, Ok(false))
+        } else {
+            let record = MultiPlexedRecord::AppendRecords {
+                position,
+                queue,
+                records,
+            };
+            self.writer().index_records(queue, position..=last_position);
+            let write_res = self.write_record(record);
+            self.writer().commit_indexed_records(write_res.is_ok());
+            let num_bytes_written = write_res?;
+            self.durability_watcher.record_written(queue, last_position);
+            self.next_persist
+                .record_written(num_bytes_written, records.count() as u64);
+            // The records are in the WAL buffer: even if persisting them fails, they will end up
+            // on disk, so they must be added to the in-memory queue.
+            (num_bytes_written, self.persist_for_class(durability_class))
         };
-        let num_bytes_written = self.record_log_writer.write_record(record)?;
-        self.persist_on_policy()?;
 
         let mem_queue = self.in_mem_queues.get_queue_mut(queue)?;
         let mut max_position = position;
         for record in records {
             // we just serialized it, we know it's valid
             let (position, payload) = record.unwrap();
-            mem_queue.append_record(&file_number, position, payload)?;
+            if let Some(file_number) = &file_number_opt {
+                mem_queue.append_record(file_number, position, payload)?;
+            } else {
+                mem_queue.append_volatile_record(position, payload)?;
+            }
             max_position = position;
         }
+        mem_queue.touch_append();
+        self.publish_to_reader(queue);
 
         self.multi_record_spare_buffer = multi_record_spare_buffer;
+        let persisted = persist_res?;
+        self.listener
+            .on_records_appended(queue, position..=max_position, num_bytes_written);
+        self.check_gc_blockage();
         Ok(AppendOutcome {
             last_position: Some(max_position),
             wal_bytes_written: num_bytes_written,
+            persisted,
         })
     }
 
+    /// Records the state of the queues that could get lost when files are deleted: the position
+    /// of empty queues, and durability classes.
     fn record_empty_queues_position(&mut self) -> io::Result<u64> {
         let mut num_bytes_written: u64 = 0;
 
         for (queue_id, queue) in self.in_mem_queues.empty_queues() {
+            if queue.durability_class() == DurabilityClass::Volatile {
+                continue;
+            }
             let next_position = queue.next_position();
             let record = MultiPlexedRecord::RecordPosition {
                 queue: queue_id,
                 position: next_position,
             };
-            num_bytes_written += self.record_log_writer.write_record(record)?;
+            num_bytes_written += lock_writer(&self.record_log_writer).write_record(record)?;
+        }
+        for (queue_id, durability_class) in self.in_mem_queues.non_default_durability_classes() {
+            if durability_class == DurabilityClass::Volatile {
+                continue;
+            }
+            let record = MultiPlexedRecord::DurabilityClass {
+                queue: queue_id,
+                class: durability_class,
+            };
+            num_bytes_written += lock_writer(&self.record_log_writer).write_record(record)?;
         }
         if num_bytes_written > 0 {
             // We need to fsync here! We are remove files from the FS
@@ -252,53 +560,130 @@ impl MultiRecordLog {
     /// asynchronously when they become exclusively composed of deleted records.
     ///
     /// This method will always truncate the record log and release the associated memory.
+    ///
+    /// Truncating keeps working when the disk is full: the truncation is then applied in memory
+    /// only, and written to the WAL once the log recovers. Files are not GCed in the meantime.
     pub fn truncate(
         &mut self,
         queue: &str,
         truncate_range: RangeToInclusive<u64>,
     ) -> Result<TruncateOutcome, TruncateError> {
         info!(range=?truncate_range, queue = queue, "truncate queue");
-        if !self.queue_exists(queue) {
+        let outcome_res = self.truncate_inner(queue, truncate_range);
+        if outcome_res.is_ok() {
+            if let Ok(mem_queue) = self.in_mem_queues.get_queue_mut(queue) {
+                mem_queue.touch_truncate();
+            }
+        }
+        // The in-memory queue may have been truncated even if an error is returned.
+        self.publish_to_reader(queue);
+        let outcome = outcome_res?;
+        self.listener.on_truncated(
+            queue,
+            truncate_range.end,
+            outcome.evicted_records,
+            outcome.wal_bytes_written,
+        );
+        self.check_gc_blockage();
+        Ok(outcome)
+    }
+
+    fn truncate_inner(
+        &mut self,
+        queue: &str,
+        truncate_range: RangeToInclusive<u64>,
+    ) -> Result<TruncateOutcome, TruncateError> {
+        let Ok(durability_class) = self.in_mem_queues.durability_class(queue) else {
             return Err(TruncateError::MissingQueue(queue.to_string()));
+        };
+        if durability_class == DurabilityClass::Volatile {
+            let evicted_records = self
+                .in_mem_queues
+                .truncate(queue, truncate_range)
+                .unwrap_or(0);
+            return Ok(TruncateOutcome {
+                evicted_records,
+                wal_bytes_written: 0,
+            });
         }
-        let mut num_bytes_written =
-            self.record_log_writer
-                .write_record(MultiPlexedRecord::Truncate {
-                    truncate_range,
-                    queue,
-                })?;
+        let write_res = self.recover_if_degraded().and_then(|_| {
+            self.write_record(MultiPlexedRecord::Truncate {
+                truncate_range,
+                queue,
+            })
+        });
+        let mut num_bytes_written = match write_res {
+            Ok(num_bytes_written) => num_bytes_written,
+            Err(io_err) if is_disk_full(&io_err) => {
+                let evicted_records = self.truncate_while_degraded(queue, truncate_range);
+                return Ok(TruncateOutcome {
+                    evicted_records,
+                    wal_bytes_written: 0,
+                });
+            }
+            Err(io_err) => return Err(io_err.into()),
+        };
+        self.next_persist.record_written(num_bytes_written, 0);
         let evicted_records = self
             .in_mem_queues
             .truncate(queue, truncate_range)
             .unwrap_or(0);
-        num_bytes_written += self.run_gc_if_necessary()?;
-        self.persist_on_policy()?;
+        // The truncate record is in the WAL buffer. If the disk got full while GCing or
+        // persisting, both will happen again when the log recovers.
+        match self.run_gc_if_necessary().and_then(|gc_num_bytes_written| {
+            num_bytes_written += gc_num_bytes_written;
+            self.persist_on_policy()
+        }) {
+            Ok(_) => {}
+            Err(io_err) if is_disk_full(&io_err) => {}
+            Err(io_err) => return Err(io_err.into()),
+        }
         Ok(TruncateOutcome {
             evicted_records,
             wal_bytes_written: num_bytes_written,
         })
     }
 
+    fn truncate_while_degraded(
+        &mut self,
+        queue: &str,
+        truncate_range: RangeToInclusive<u64>,
+    ) -> usize {
+        let pending_position = self
+            .pending_truncates
+            .entry(queue.to_string())
+            .or_insert(truncate_range.end);
+        *pending_position = (*pending_position).max(truncate_range.end);
+        self.in_mem_queues
+            .truncate(queue, truncate_range)
+            .unwrap_or(0)
+    }
+
     /// Returns the number of bytes the GC pass appended to the WAL — empty-queue position
     /// records, if any. Returns 0 when there's no GC work to do.
     fn run_gc_if_necessary(&mut self) -> io::Result<u64> {
         debug!("run_gc_if_necessary");
         let mut num_bytes_written = 0;
 
-        if self
-            .record_log_writer
-            .directory()
-            .has_files_that_can_be_deleted()
-        {
+        if self.writer().directory().has_files_that_can_be_deleted() {
             // We are about to delete files.
             // Let's make sure we record the offsets of the empty queues
             // so that we don't lose that information after dropping the files.
             //
             // But first we clone the current file number to make sure that the file that will
             // contain the truncate positions it self won't be GC'ed.
-            let _file_number = self.record_log_writer.current_file().clone();
-            num_bytes_written += self.record_empty_queues_position()?;
-            self.record_log_writer.directory().gc()?;
+            let _file_number = self.writer().current_file().clone();
+            let record_res = self.record_empty_queues_position();
+            let gc_res =
+                self.degrade_on_disk_full(record_res)
+                    .and_then(|record_num_bytes_written| {
+                        num_bytes_written += record_num_bytes_written;
+                        self.writer().directory().gc()
+                    });
+            // Files may have been rolled and removed, even if an error occurred.
+            let wal_files = wal_files(&self.writer());
+            self.publish_wal_files(wal_files);
+            gc_res?;
         }
         // only execute the following if we are above the debug  level in tokio tracing
         if event_enabled!(Level::DEBUG) {
@@ -323,18 +708,148 @@ impl MultiRecordLog {
         self.in_mem_queues.range(queue, range)
     }
 
-    /// Flush if the policy says it should be done
-    fn persist_on_policy(&mut self) -> io::Result<()> {
+    /// Returns the records of a queue within a range of positions, as owned records.
+    ///
+    /// Unlike [`Self::range`], the records do not borrow the log: they can be kept, or sent to
+    /// another task, while records are appended or truncated. Their payloads are not copied.
+    ///
+    /// A record keeps the memory holding its payload alive until it is dropped, even after it is
+    /// truncated. That memory is then no longer accounted for by [`Self::resource_usage`].
+    pub fn range_owned<R>(
+        &self,
+        queue: &str,
+        range: R,
+    ) -> Result<impl Iterator<Item = OwnedRecord> + '_, MissingQueue>
+    where
+        R: RangeBounds<u64> + 'static,
+    {
+        self.in_mem_queues.range_owned(queue, range)
+    }
+
+    /// Returns the records of a queue starting at position `from`, up to `max_records` records
+    /// and `max_bytes` bytes of payload.
+    ///
+    /// The first record is returned even if its payload is larger than `max_bytes`. The page
+    /// tells where to read the next page from, and whether the end of the queue was reached.
+    pub fn range_with_limits(
+        &self,
+        queue: &str,
+        from: u64,
+        max_records: usize,
+        max_bytes: usize,
+    ) -> Result<RecordPage, MissingQueue> {
+        self.in_mem_queues
+            .range_with_limits(queue, from, max_records, max_bytes)
+    }
+
+    /// Persists the appends to a queue of the given durability class.
+    ///
+    /// Returns true if data was persisted.
+    fn persist_for_class(&mut self, durability_class: DurabilityClass) -> io::Result<bool> {
+        let persist_action = match durability_class {
+            DurabilityClass::AlwaysFsync => PersistAction::FlushAndFsync,
+            DurabilityClass::Flush => PersistAction::Flush,
+            DurabilityClass::Delayed => return self.persist_on_policy(),
+            DurabilityClass::Volatile => return Ok(false),
+        };
+        self.persist(persist_action)?;
+        Ok(true)
+    }
+
+    /// Flush if the policy says it should be done.
+    ///
+    /// Returns true if data was persisted.
+    fn persist_on_policy(&mut self) -> io::Result<bool> {
         if let Some(persist_action) = self.next_persist.should_persist() {
             self.persist(persist_action)?;
-            self.next_persist.update_persisted();
+            return Ok(true);
         }
-        Ok(())
+        Ok(false)
     }
 
     /// Flush and optionnally fsync data
     pub fn persist(&mut self, persist_action: PersistAction) -> io::Result<()> {
-        self.record_log_writer.persist(persist_action)
+        let persist_res = persist_and_track_durability(
+            &self.record_log_writer,
+            &self.durability_watcher,
+            persist_action,
+        );
+        self.degrade_on_disk_full(persist_res)?;
+        // The persist covers every queue: the policy does not need to persist the same data
+        // again, unless it requires an fsync that did not happen.
+        if persist_action.is_fsync() || !self.next_persist.requires_fsync() {
+            self.next_persist.update_persisted();
+        }
+        Ok(())
+    }
+
+    /// Returns the last position of the queue that reached the disk, i.e. that is covered by a
+    /// completed `FlushAndFsync` persist.
+    ///
+    /// Returns `None` if none of the records of the queue is durable yet.
+    pub fn durable_position(&self, queue: &str) -> Result<Option<u64>, MissingQueue> {
+        if !self.queue_exists(queue) {
+            return Err(MissingQueue(queue.to_string()));
+        }
+        Ok(self.durability_watcher.durable_position(queue))
+    }
+
+    /// Returns the IO statistics of the log since it was opened.
+    pub fn stats(&self) -> IoStats {
+        self.io_stats.snapshot()
+    }
+
+    /// Returns a handle to wait for positions to become durable, from any thread.
+    pub fn durability_watcher(&self) -> DurabilityWatcher {
+        self.durability_watcher.clone()
+    }
+
+    /// Returns a handle to read the queues from any thread, while the log keeps being written.
+    ///
+    /// Once a reader was requested, every operation on a queue updates the state shared with the
+    /// readers, which holds a reference to every record of the queue.
+    pub fn reader(&mut self) -> LogReader {
+        if let Some(reader) = &self.reader_opt {
+            return reader.clone();
+        }
+        let reader = LogReader::default();
+        let global_summary = self.global_summary();
+        self.published_wal_files =
+            Some((global_summary.num_files, global_summary.current_file_number));
+        reader.publish_global(global_summary);
+        for queue in self.in_mem_queues.list_queues() {
+            let mem_queue_opt = self.in_mem_queues.get_queue(queue).ok();
+            reader.publish_queue(queue, mem_queue_opt);
+        }
+        self.reader_opt = Some(reader.clone());
+        reader
+    }
+
+    /// Publishes the global summary to the readers, if any, when the wal files changed since it
+    /// was last published.
+    fn publish_wal_files(&mut self, wal_files: (usize, u64)) {
+        let Some(reader) = &self.reader_opt else {
+            return;
+        };
+        if self.published_wal_files == Some(wal_files) {
+            return;
+        }
+        let (num_files, current_file_number) = wal_files;
+        reader.publish_global(GlobalSummary {
+            num_files,
+            current_file_number,
+            persist_policy: self.persist_policy.clone(),
+        });
+        self.published_wal_files = Some(wal_files);
+    }
+
+    /// Publishes the state of a queue to the readers, if any.
+    fn publish_to_reader(&self, queue: &str) {
+        let Some(reader) = &self.reader_opt else {
+            return;
+        };
+        let mem_queue_opt = self.in_mem_queues.get_queue(queue).ok();
+        reader.publish_queue(queue, mem_queue_opt);
     }
 
     /// Returns the position of the last record appended to the queue.
@@ -347,9 +862,123 @@ impl MultiRecordLog {
         self.in_mem_queues.last_record(queue)
     }
 
+    /// Returns the resources used by every queue, and the disk space that would be freed by
+    /// truncating or deleting each of them.
+    pub fn resource_usage_per_queue(&self) -> BTreeMap<String, QueueResourceUsage> {
+        let writer = self.writer();
+        // The current file is never GCed.
+        let current_file_number = writer.get_underlying_wrt().current_file().file_number();
+        let mut oldest_files: Vec<u64> = self
+            .in_mem_queues
+            .iter()
+            .filter_map(|(_, mem_queue)| mem_queue.first_file_number())
+            .collect();
+        oldest_files.sort_unstable();
+        let oldest_file = oldest_files.first().copied();
+        let second_oldest_file = oldest_files.get(1).copied();
+        let gc_bound = oldest_file
+            .unwrap_or(current_file_number)
+            .min(current_file_number);
+        self.in_mem_queues
+            .iter()
+            .map(|(queue, mem_queue)| {
+                let oldest_pinned_file = mem_queue.first_file_number();
+                // Only the queue holding the oldest file, alone, releases files when it goes.
+                let reclaimable_disk_bytes = if oldest_pinned_file.is_some()
+                    && oldest_pinned_file == oldest_file
+                    && second_oldest_file != oldest_file
+                {
+                    let gc_bound_without_queue = second_oldest_file
+                        .unwrap_or(current_file_number)
+                        .min(current_file_number);
+                    writer
+                        .get_underlying_wrt()
+                        .size_before(gc_bound_without_queue)
+                        - writer.get_underlying_wrt().size_before(gc_bound)
+                } else {
+                    0
+                };
+                let queue_resource_usage = QueueResourceUsage {
+                    memory_used_bytes: queue.len() + mem_queue.size(),
+                    memory_allocated_bytes: queue.len() + mem_queue.capacity(),
+                    num_records: mem_queue.num_records(),
+                    oldest_pinned_file,
+                    reclaimable_disk_bytes,
+                };
+                (queue.to_string(), queue_resource_usage)
+            })
+            .collect()
+    }
+
+    /// Returns the queue preventing the oldest wal file from being GCed, if any.
+    ///
+    /// Files are GCed in order: as long as a queue holds a record in the oldest file, every
+    /// file after it is kept as well.
+    pub fn gc_blockage(&self) -> Option<GcBlockage> {
+        let writer = self.writer();
+        let rolling_writer = writer.get_underlying_wrt();
+        let directory = &rolling_writer.directory;
+        let oldest_file_number = directory.first_file_number().file_number();
+        let current_file_number = rolling_writer.current_file().file_number();
+        if oldest_file_number == current_file_number {
+            return None;
+        }
+        let queue = self
+            .in_mem_queues
+            .iter()
+            .filter(|(_, mem_queue)| mem_queue.first_file_number() == Some(oldest_file_number))
+            .map(|(queue, _)| queue)
+            .min()?;
+        Some(GcBlockage {
+            queue: queue.to_string(),
+            file_number: oldest_file_number,
+            pinned_for: directory.oldest_file_blocked_for().unwrap_or_default(),
+            num_pinned_files: directory.files.count_before(current_file_number),
+        })
+    }
+
+    /// Reports the gc blockage if it exceeds the configured threshold.
+    fn check_gc_blockage(&mut self) {
+        let Some(gc_blockage_threshold) = self.gc_blockage_threshold else {
+            return;
+        };
+        // Cheap checks first, as this runs on every append.
+        {
+            let writer = self.writer();
+            let directory = &writer.get_underlying_wrt().directory;
+            let oldest_file_number = directory.first_file_number().file_number();
+            if let Some((queue, file_number)) = &self.reported_gc_blockage {
+                let still_pinned = self
+                    .in_mem_queues
+                    .get_queue(queue)
+                    .map(|mem_queue| mem_queue.first_file_number() == Some(*file_number))
+                    .unwrap_or(false);
+                if *file_number == oldest_file_number && still_pinned {
+                    return;
+                }
+            }
+            let blocked_for = directory.oldest_file_blocked_for().unwrap_or_default();
+            if blocked_for < gc_blockage_threshold {
+                return;
+            }
+        }
+        let Some(gc_blockage) = self.gc_blockage() else {
+            return;
+        };
+        self.reported_gc_blockage = Some((gc_blockage.queue.clone(), gc_blockage.file_number));
+        warn!(
+            queue = gc_blockage.queue,
+            file_number = gc_blockage.file_number,
+            pinned_for = ?gc_blockage.pinned_for,
+            num_pinned_files = gc_blockage.num_pinned_files,
+            "queue prevents wal files from being gced"
+        );
+        self.listener.on_gc_blocked(&gc_blockage);
+    }
+
     /// Return the amount of memory and disk space used by mrecordlog.
     pub fn resource_usage(&self) -> ResourceUsage {
-        let disk_used_bytes = self.record_log_writer.size();
+        let disk_used_bytes = self.writer().size();
         let (memory_used_bytes, memory_allocated_bytes) = self.in_mem_queues.size();
         ResourceUsage {
             memory_used_bytes,
@@ -0,0 +1,73 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{LogListener, PersistPolicy};
+
+/// What happens to a wal file once all of its records have been truncated or deleted.
+#[derive(Clone, Default)]
+pub enum GcAction {
+    /// Remove the file.
+    #[default]
+    Delete,
+    /// Move the file to an archive directory.
+    ///
+    /// Once the file is archived, the oldest archived files are removed until the archive
+    /// fits within `max_num_bytes`, and archived files older than `max_age` are removed.
+    Archive {
+        directory: PathBuf,
+        max_num_bytes: u64,
+        max_age: Duration,
+    },
+    /// Call the function with the path of the file, then remove it.
+    Callback(Arc<dyn Fn(&Path) + Send + Sync>),
+}
+
+impl std::fmt::Debug for GcAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Delete => f.write_str("Delete"),
+            Self::Archive {
+                directory,
+                max_num_bytes,
+                max_age,
+            } => f
+                .debug_struct("Archive")
+                .field("directory", directory)
+                .field("max_num_bytes", max_num_bytes)
+                .field("max_age", max_age)
+                .finish(),
+            Self::Callback(_) => f.write_str("Callback"),
+        }
+    }
+}
+
+/// Options used to open a [`MultiRecordLog`](crate::MultiRecordLog).
+#[derive(Clone, Debug)]
+pub struct MultiRecordLogOptions {
+    pub persist_policy: PersistPolicy,
+    pub gc_action: GcAction,
+    /// If true, and the persist policy persists on a delay (`OnDelay`, or `Batched` with its
+    /// `max_delay`), a background thread persists buffered data once the delay is elapsed, even
+    /// if no operation arrives. Operations then no longer persist because the delay is elapsed.
+    /// The thread stops when the log is dropped.
+    pub background_persist: bool,
+    /// Notified of the events happening within the log, including the corruptions detected
+    /// while opening it.
+    pub listener: Option<Arc<dyn LogListener>>,
+    /// If set, a warning is logged and the listener is notified when a queue prevents the
+    /// oldest wal file from being GCed for longer than this.
+    pub gc_blockage_threshold: Option<Duration>,
+}
+
+impl Default for MultiRecordLogOptions {
+    fn default() -> Self {
+        MultiRecordLogOptions {
+            persist_policy: PersistPolicy::default(),
+            gc_action: GcAction::Delete,
+            background_persist: false,
+            listener: None,
+            gc_blockage_threshold: None,
+        }
+    }
+}
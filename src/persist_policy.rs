@@ -1,6 +1,8 @@
 use std::time::{Duration, Instant};
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PersistAction {
     /// The buffer will be flushed to the OS, but not necessarily to the disk.
     Flush,
@@ -32,7 +34,7 @@ impl PersistAction {
 ///
 /// The `PersistPolicy` defines the trade-off applied for the second kind of
 /// operations.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum PersistPolicy {
     /// Only ensure data is persisted when critical records are written.
     ///
@@ -46,6 +48,79 @@ pub enum PersistPolicy {
     },
     /// Persist data after each action
     Always(PersistAction),
+    /// Persist data as soon as any of the thresholds is crossed, and when critical records are
+    /// written.
+    ///
+    /// This bounds the amount of data that can be lost, both in bytes and in time.
+    Batched {
+        /// Maximum number of bytes written to the WAL without being persisted.
+        max_bytes: u64,
+        /// Maximum number of records appended without being persisted.
+        max_records: u64,
+        /// Maximum time data written to the WAL can stay unpersisted. It is checked on writes
+        /// only.
+        max_delay: Duration,
+        action: PersistAction,
+    },
+}
+
+/// How the appends to a given queue are persisted.
+///
+/// Fsyncs are shared: when an append to an `AlwaysFsync` queue fsyncs the WAL, the data
+/// previously appended to every other queue gets persisted with it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DurabilityClass {
+    /// Appends are flushed and fsynced right away.
+    AlwaysFsync,
+    /// Appends are flushed to the OS right away, but not fsynced.
+    Flush,
+    /// Appends are persisted following the [`PersistPolicy`] of the log.
+    #[default]
+    Delayed,
+    /// Appends are kept in memory only and never written to the WAL. The queue itself is not
+    /// recorded in the WAL either, and is lost when the log is reopened.
+    Volatile,
+}
+
+impl DurabilityClass {
+    pub(crate) fn to_code(self) -> u8 {
+        match self {
+            DurabilityClass::AlwaysFsync => 1,
+            DurabilityClass::Flush => 2,
+            DurabilityClass::Delayed => 3,
+            DurabilityClass::Volatile => 4,
+        }
+    }
+
+    pub(crate) fn from_code(code: u8) -> Option<DurabilityClass> {
+        match code {
+            1 => Some(DurabilityClass::AlwaysFsync),
+            2 => Some(DurabilityClass::Flush),
+            3 => Some(DurabilityClass::Delayed),
+            4 => Some(DurabilityClass::Volatile),
+            _ => None,
+        }
+    }
+}
+
+impl Default for PersistPolicy {
+    /// Flush after each operation, but do not fsync.
+    fn default() -> Self {
+        PersistPolicy::Always(PersistAction::Flush)
+    }
+}
+
+impl PersistPolicy {
+    /// Interval at which data must be persisted even if no operation arrives, if any.
+    pub(crate) fn persist_interval(&self) -> Option<(Duration, PersistAction)> {
+        match self {
+            PersistPolicy::OnDelay { interval, action } => Some((*interval, *action)),
+            PersistPolicy::Batched {
+                max_delay, action, ..
+            } => Some((*max_delay, *action)),
+            PersistPolicy::DoNothing | PersistPolicy::Always(_) => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -56,6 +131,16 @@ pub(crate) enum PersistState {
         interval: Duration,
         action: PersistAction,
     },
+    Batched {
+        max_bytes: u64,
+        max_records: u64,
+        max_delay: Duration,
+        action: PersistAction,
+        unpersisted_bytes: u64,
+        unpersisted_records: u64,
+        // Time at which the oldest unpersisted data was written.
+        unpersisted_since: Option<Instant>,
+    },
     NoOp,
 }
 
@@ -74,13 +159,95 @@ impl PersistState {
                     None
                 }
             }
+            PersistState::Batched {
+                max_bytes,
+                max_records,
+                max_delay,
+                action,
+                unpersisted_bytes,
+                unpersisted_records,
+                unpersisted_since,
+            } => {
+                let delay_elapsed = unpersisted_since
+                    .map(|since| since.elapsed() >= *max_delay)
+                    .unwrap_or(false);
+                if *unpersisted_bytes >= *max_bytes
+                    || *unpersisted_records >= *max_records
+                    || delay_elapsed
+                {
+                    Some(*action)
+                } else {
+                    None
+                }
+            }
             PersistState::NoOp => None,
         }
     }
 
+    /// Returns true if the policy persists data by fsyncing it.
+    pub fn requires_fsync(&self) -> bool {
+        match self {
+            PersistState::OnAppend(action)
+            | PersistState::OnDelay { action, .. }
+            | PersistState::Batched { action, .. } => action.is_fsync(),
+            PersistState::NoOp => false,
+        }
+    }
+
+    /// Accounts for data written to the WAL and not persisted yet.
+    pub fn record_written(&mut self, num_bytes: u64, num_records: u64) {
+        if let PersistState::Batched {
+            unpersisted_bytes,
+            unpersisted_records,
+            unpersisted_since,
+            ..
+        } = self
+        {
+            *unpersisted_bytes += num_bytes;
+            *unpersisted_records += num_records;
+            unpersisted_since.get_or_insert_with(Instant::now);
+        }
+    }
+
+    /// Drops the persists due once a delay is elapsed, when a background persister handles
+    /// them.
+    pub fn without_delay(self) -> PersistState {
+        match self {
+            PersistState::OnDelay { .. } => PersistState::NoOp,
+            PersistState::Batched {
+                max_bytes,
+                max_records,
+                action,
+                unpersisted_bytes,
+                unpersisted_records,
+                unpersisted_since,
+                ..
+            } => PersistState::Batched {
+                max_bytes,
+                max_records,
+                max_delay: Duration::MAX,
+                action,
+                unpersisted_bytes,
+                unpersisted_records,
+                unpersisted_since,
+            },
+            persist_state => persist_state,
+        }
+    }
+
     pub fn update_persisted(&mut self) {
         match self {
             PersistState::OnAppend(_) | PersistState::NoOp => (),
+            PersistState::Batched {
+                unpersisted_bytes,
+                unpersisted_records,
+                unpersisted_since,
+                ..
+            } => {
+                *unpersisted_bytes = 0;
+                *unpersisted_records = 0;
+                *unpersisted_since = None;
+            }
             PersistState::OnDelay {
                 ref mut next_persist,
                 interval,
@@ -99,6 +266,20 @@ impl From<PersistPolicy> for PersistState {
                 interval,
                 action,
             },
+            PersistPolicy::Batched {
+                max_bytes,
+                max_records,
+                max_delay,
+                action,
+            } => PersistState::Batched {
+                max_bytes,
+                max_records,
+                max_delay,
+                action,
+                unpersisted_bytes: 0,
+                unpersisted_records: 0,
+                unpersisted_since: None,
+            },
             PersistPolicy::DoNothing => PersistState::NoOp,
         }
     }
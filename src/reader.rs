@@ -0,0 +1,183 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::ops::{Bound, RangeBounds};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::error::MissingQueue;
+use crate::mem::MemQueue;
+use crate::{GlobalSummary, OwnedRecord, QueueSummary, QueuesSummary, RecordPage};
+
+#[derive(Default)]
+struct QueueState {
+    // Shared with the readers going through the records, which do not hold the lock meanwhile.
+    records: Arc<VecDeque<OwnedRecord>>,
+    next_position: u64,
+    summary: QueueSummary,
+}
+
+#[derive(Default)]
+struct Inner {
+    queues: BTreeMap<String, QueueState>,
+    global: GlobalSummary,
+}
+
+/// Reads the queues of a [`MultiRecordLog`](crate::MultiRecordLog) while it keeps being written.
+///
+/// The log publishes the state of a queue to its readers after every operation on it. Readers
+/// only hold a lock while they take a reference to the records of a queue, and go through them
+/// once it is released: reading a large range does not hold the log back. If the log publishes
+/// the queue in the meantime, it copies the list of its records, but not their payloads.
+///
+/// The reader is cheap to clone and can be moved to other threads.
+#[derive(Clone, Default)]
+pub struct LogReader {
+    inner: Arc<RwLock<Inner>>,
+}
+
+impl LogReader {
+    fn read(&self) -> RwLockReadGuard<'_, Inner> {
+        self.inner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Inner> {
+        self.inner
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns the records of a queue, and its next position.
+    fn queue_records(
+        &self,
+        queue: &str,
+    ) -> Result<(Arc<VecDeque<OwnedRecord>>, u64), MissingQueue> {
+        let inner = self.read();
+        let queue_state = inner
+            .queues
+            .get(queue)
+            .ok_or_else(|| MissingQueue(queue.to_string()))?;
+        Ok((queue_state.records.clone(), queue_state.next_position))
+    }
+
+    /// Returns the records of a queue within a range of positions.
+    pub fn range<R>(&self, queue: &str, range: R) -> Result<Vec<OwnedRecord>, MissingQueue>
+    where R: RangeBounds<u64> {
+        let (records, _) = self.queue_records(queue)?;
+        let start_idx = match range.start_bound() {
+            Bound::Included(&start) => records.partition_point(|record| record.position < start),
+            Bound::Excluded(&start) => records.partition_point(|record| record.position <= start),
+            Bound::Unbounded => 0,
+        };
+        Ok(records
+            .range(start_idx..)
+            .take_while(|record| range.contains(&record.position))
+            .cloned()
+            .collect())
+    }
+
+    /// Returns the records of a queue within limits, like
+    /// [`MultiRecordLog::range_with_limits`](crate::MultiRecordLog::range_with_limits).
+    pub fn range_with_limits(
+        &self,
+        queue: &str,
+        from: u64,
+        max_records: usize,
+        max_bytes: usize,
+    ) -> Result<RecordPage, MissingQueue> {
+        let (records, next_position) = self.queue_records(queue)?;
+        let start_idx = records.partition_point(|record| record.position < from);
+        Ok(RecordPage::from_records(
+            records.range(start_idx..).cloned(),
+            from,
+            next_position,
+            max_records,
+            max_bytes,
+        ))
+    }
+
+    /// Returns the position of the last record appended to the queue.
+    pub fn last_position(&self, queue: &str) -> Result<Option<u64>, MissingQueue> {
+        let inner = self.read();
+        let queue_state = inner
+            .queues
+            .get(queue)
+            .ok_or_else(|| MissingQueue(queue.to_string()))?;
+        Ok(queue_state.next_position.checked_sub(1))
+    }
+
+    /// Returns the summary of the log, as of the last operation on it.
+    pub fn summary(&self) -> QueuesSummary {
+        let inner = self.read();
+        QueuesSummary {
+            global: inner.global.clone(),
+            queues: inner
+                .queues
+                .iter()
+                .map(|(queue, queue_state)| (queue.clone(), queue_state.summary.clone()))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn publish_global(&self, global: GlobalSummary) {
+        self.write().global = global;
+    }
+
+    /// Publishes the state of a queue, or its deletion if `mem_queue` is `None`.
+    pub(crate) fn publish_queue(&self, queue: &str, mem_queue_opt: Option<&MemQueue>) {
+        let Some(mem_queue) = mem_queue_opt else {
+            self.write().queues.remove(queue);
+            return;
+        };
+        let next_position = mem_queue.next_position();
+        let summary = mem_queue.summary();
+        let mut inner = self.write();
+        if !inner.queues.contains_key(queue) {
+            inner
+                .queues
+                .insert(queue.to_string(), QueueState::default());
+        }
+        let queue_state = inner.queues.get_mut(queue).unwrap();
+        if let Some(records) = Arc::get_mut(&mut queue_state.records) {
+            update_records(records, mem_queue);
+            queue_state.next_position = next_position;
+            queue_state.summary = summary;
+            return;
+        }
+        // A reader is going through the records. They are copied without holding the lock, so
+        // that other readers are not blocked meanwhile. The log is the only one to publish: the
+        // queue cannot change until the copy gets published.
+        let records_snapshot = queue_state.records.clone();
+        drop(inner);
+        let mut records = VecDeque::clone(&records_snapshot);
+        drop(records_snapshot);
+        update_records(&mut records, mem_queue);
+        let queue_state = QueueState {
+            records: Arc::new(records),
+            next_position,
+            summary,
+        };
+        self.write().queues.insert(queue.to_string(), queue_state);
+    }
+}
+
+/// Brings the published records of a queue up to date with the queue.
+fn update_records(records: &mut VecDeque<OwnedRecord>, mem_queue: &MemQueue) {
+    // Positions only increase: the records to remove are the ones before the first record of
+    // the queue, and the records to add the ones after the last record published.
+    let first_position = mem_queue
+        .range(..)
+        .next()
+        .map(|record| record.position)
+        .unwrap_or_else(|| mem_queue.next_position());
+    while let Some(record) = records.front() {
+        if record.position >= first_position {
+            break;
+        }
+        records.pop_front();
+    }
+    let new_records_start = records
+        .back()
+        .map(|record| Bound::Excluded(record.position))
+        .unwrap_or(Bound::Unbounded);
+    records.extend(mem_queue.range_owned((new_records_start, Bound::Unbounded)));
+}
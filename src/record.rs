@@ -5,7 +5,7 @@ use bytes::Buf;
 use tracing::error;
 
 use crate::error::MultiRecordCorruption;
-use crate::Serializable;
+use crate::{DurabilityClass, Serializable};
 
 #[derive(Copy, Clone, Eq, PartialEq)]
 pub(crate) enum MultiPlexedRecord<'a> {
@@ -29,6 +29,13 @@ pub(crate) enum MultiPlexedRecord<'a> {
         queue: &'a str,
         position: u64, //< not useful tbh
     },
+    /// Records the durability class of a queue.
+    ///
+    /// It is only recorded for queues that don't have the default class.
+    DurabilityClass {
+        queue: &'a str,
+        class: DurabilityClass,
+    },
 }
 
 impl std::fmt::Debug for MultiPlexedRecord<'_> {
@@ -62,6 +69,11 @@ impl std::fmt::Debug for MultiPlexedRecord<'_> {
                 .field("queue", queue)
                 .field("position", position)
                 .finish(),
+            Self::DurabilityClass { queue, class } => f
+                .debug_struct("DurabilityClass")
+                .field("queue", queue)
+                .field("class", class)
+                .finish(),
         }
     }
 }
@@ -74,6 +86,7 @@ impl<'a> MultiPlexedRecord<'a> {
             Self::Truncate { queue, .. } => queue,
             Self::RecordPosition { queue, .. } => queue,
             Self::DeleteQueue { queue, .. } => queue,
+            Self::DurabilityClass { queue, .. } => queue,
         }
     }
 }
@@ -85,6 +98,7 @@ enum RecordType {
     Touch = 2,
     DeleteQueue = 3,
     AppendRecords = 4,
+    DurabilityClass = 5,
 }
 
 impl TryFrom<u8> for RecordType {
@@ -96,6 +110,7 @@ impl TryFrom<u8> for RecordType {
             2 => Ok(RecordType::Touch),
             3 => Ok(RecordType::DeleteQueue),
             4 => Ok(RecordType::AppendRecords),
+            5 => Ok(RecordType::DurabilityClass),
             _ => Err(()),
         }
     }
@@ -146,6 +161,15 @@ impl<'a> Serializable<'a> for MultiPlexedRecord<'a> {
             MultiPlexedRecord::DeleteQueue { position, queue } => {
                 serialize(RecordType::DeleteQueue, position, queue, &[], buffer);
             }
+            MultiPlexedRecord::DurabilityClass { queue, class } => {
+                serialize(
+                    RecordType::DurabilityClass,
+                    0,
+                    queue,
+                    &[class.to_code()],
+                    buffer,
+                );
+            }
         }
     }
 
@@ -185,6 +209,15 @@ impl<'a> Serializable<'a> for MultiPlexedRecord<'a> {
             }),
             RecordType::Touch => Some(MultiPlexedRecord::RecordPosition { queue, position }),
             RecordType::DeleteQueue => Some(MultiPlexedRecord::DeleteQueue { queue, position }),
+            RecordType::DurabilityClass => {
+                let [class_code] = payload else {
+                    return None;
+                };
+                Some(MultiPlexedRecord::DurabilityClass {
+                    queue,
+                    class: DurabilityClass::from_code(*class_code)?,
+                })
+            }
         }
     }
 }
@@ -300,7 +333,7 @@ mod tests {
                 num_record_types += 1;
             }
         }
-        assert_eq!(num_record_types, 4);
+        assert_eq!(num_record_types, 5);
     }
 
     #[test]
@@ -361,6 +394,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_durability_class_record_serialization() {
+        let record = MultiPlexedRecord::DurabilityClass {
+            queue: "queue_name",
+            class: crate::DurabilityClass::AlwaysFsync,
+        };
+        let mut buffer: Vec<u8> = vec![];
+        record.serialize(&mut buffer);
+        assert_eq!(MultiPlexedRecord::deserialize(&buffer), Some(record));
+        assert_eq!(
+            MultiPlexedRecord::deserialize(&buffer[..buffer.len() - 1]),
+            None
+        );
+    }
+
     #[test]
     fn test_multiplexedrecord_deserialization_corruption() {
         let mut buffer_multirecord: Vec<u8> = vec![];
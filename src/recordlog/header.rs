@@ -0,0 +1,156 @@
+use std::convert::TryInto;
+
+/// Marks records carrying a `RecordHeader`.
+///
+/// Records written by older versions of mrecordlog have no header. They start with a
+/// `MultiPlexedRecord` type tag, which never takes this value.
+const CHECKED_RECORD_MARKER: u8 = 0x80;
+
+/// Marks epoch markers: records made of a header only, which readers skip.
+const EPOCH_MARKER: u8 = 0x81;
+
+pub const RECORD_HEADER_LEN: usize = 1 + 4 + 8 + 4;
+
+/// Header prepended to every record, before it gets split into frames.
+///
+/// Frames only protect their own payload. The header adds a checksum of the whole record, which
+/// detects multi-frame records assembled from frames of different records, and an
+/// `(epoch, sequence)` id that must strictly increase along the log. The epoch is incremented
+/// by every session writing to the log, so leftovers of earlier writes found after
+/// newer records get rejected.
+///
+/// Records with a header were introduced in a later version of the format: older versions of
+/// mrecordlog cannot read a log written by this one. They are read back as corrupted records.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecordHeader {
+    pub epoch: u32,
+    pub sequence: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RecordCheck {
+    /// The record has a valid header. Its payload starts after the header.
+    Valid(RecordHeader),
+    /// The record is an epoch marker, written before the first record of a writer so that the
+    /// next writer uses a greater epoch, even if none of the records of this one reach the disk.
+    EpochMarker(RecordHeader),
+    /// The record was written without header. Its payload is the entire record.
+    Unchecked,
+    /// The record header is truncated or its checksum does not match.
+    Corrupted,
+}
+
+fn checksum(header: &RecordHeader, payload: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&header.epoch.to_le_bytes());
+    hasher.update(&header.sequence.to_le_bytes());
+    hasher.update(payload);
+    hasher.finalize()
+}
+
+impl RecordHeader {
+    pub fn id(&self) -> (u32, u64) {
+        (self.epoch, self.sequence)
+    }
+
+    /// Serializes the header of a record with the given payload.
+    pub fn serialize(&self, payload: &[u8]) -> [u8; RECORD_HEADER_LEN] {
+        self.serialize_with_marker(CHECKED_RECORD_MARKER, payload)
+    }
+
+    /// Serializes an epoch marker, a record made of this header only.
+    pub fn serialize_epoch_marker(&self) -> [u8; RECORD_HEADER_LEN] {
+        self.serialize_with_marker(EPOCH_MARKER, &[])
+    }
+
+    fn serialize_with_marker(&self, marker: u8, payload: &[u8]) -> [u8; RECORD_HEADER_LEN] {
+        let mut dest = [0u8; RECORD_HEADER_LEN];
+        dest[0] = marker;
+        dest[1..5].copy_from_slice(&self.epoch.to_le_bytes());
+        dest[5..13].copy_from_slice(&self.sequence.to_le_bytes());
+        dest[13..17].copy_from_slice(&checksum(self, payload).to_le_bytes());
+        dest
+    }
+
+    /// Checks a record made of a header followed by its payload.
+    pub fn check(record: &[u8]) -> RecordCheck {
+        let is_epoch_marker = match record.first() {
+            Some(&CHECKED_RECORD_MARKER) => false,
+            Some(&EPOCH_MARKER) => true,
+            _ => return RecordCheck::Unchecked,
+        };
+        if record.len() < RECORD_HEADER_LEN || (is_epoch_marker && record.len() > RECORD_HEADER_LEN)
+        {
+            return RecordCheck::Corrupted;
+        }
+        let (header_bytes, payload) = record.split_at(RECORD_HEADER_LEN);
+        let header = RecordHeader {
+            epoch: u32::from_le_bytes(header_bytes[1..5].try_into().unwrap()),
+            sequence: u64::from_le_bytes(header_bytes[5..13].try_into().unwrap()),
+        };
+        let expected_checksum = u32::from_le_bytes(header_bytes[13..17].try_into().unwrap());
+        if checksum(&header, payload) != expected_checksum {
+            return RecordCheck::Corrupted;
+        }
+        if is_epoch_marker {
+            return RecordCheck::EpochMarker(header);
+        }
+        RecordCheck::Valid(header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_header_serialize_check() {
+        let header = RecordHeader {
+            epoch: 3,
+            sequence: 17,
+        };
+        let mut record = header.serialize(b"hello").to_vec();
+        record.extend_from_slice(b"hello");
+        assert_eq!(RecordHeader::check(&record), RecordCheck::Valid(header));
+    }
+
+    #[test]
+    fn test_record_header_corrupted() {
+        let header = RecordHeader {
+            epoch: 3,
+            sequence: 17,
+        };
+        let mut record = header.serialize(b"hello").to_vec();
+        record.extend_from_slice(b"hello");
+        for num_truncated_bytes in 1..record.len() {
+            assert_eq!(
+                RecordHeader::check(&record[..record.len() - num_truncated_bytes]),
+                RecordCheck::Corrupted
+            );
+        }
+        let last = record.len() - 1;
+        record[last] = b'a';
+        assert_eq!(RecordHeader::check(&record), RecordCheck::Corrupted);
+    }
+
+    #[test]
+    fn test_record_header_epoch_marker() {
+        let header = RecordHeader {
+            epoch: 3,
+            sequence: 0,
+        };
+        let mut record = header.serialize_epoch_marker().to_vec();
+        assert_eq!(
+            RecordHeader::check(&record),
+            RecordCheck::EpochMarker(header)
+        );
+        record.push(0);
+        assert_eq!(RecordHeader::check(&record), RecordCheck::Corrupted);
+    }
+
+    #[test]
+    fn test_record_header_unchecked() {
+        assert_eq!(RecordHeader::check(b""), RecordCheck::Unchecked);
+        assert_eq!(RecordHeader::check(b"\x01legacy"), RecordCheck::Unchecked);
+    }
+}
@@ -1,6 +1,8 @@
+mod header;
 mod reader;
 mod writer;
 pub use self::reader::RecordReader;
+pub(crate) use self::reader::{ReadObserver, RecordIssue};
 pub use self::writer::RecordWriter;
 
 #[cfg(test)]
@@ -1,10 +1,12 @@
 use std::io;
+use std::ops::ControlFlow;
 
+use super::header::{RecordCheck, RecordHeader, RECORD_HEADER_LEN};
 use crate::error::ReadRecordError;
-use crate::frame::{FrameReader, FrameWriter, ReadFrameError};
+use crate::frame::{DecodedFrame, FrameReader, FrameType, FrameWriter, ReadFrameError};
 use crate::recordlog::RecordWriter;
 use crate::rolling::{RollingReader, RollingWriter};
-use crate::{BlockRead, Serializable};
+use crate::{BlockRead, PersistAction, Serializable};
 
 pub struct RecordReader<R> {
     frame_reader: FrameReader<R>,
@@ -13,6 +15,49 @@ pub struct RecordReader<R> {
     // This is useful, as it makes it possible to drop a record
     // if one of its fragment was corrupted.
     within_record: bool,
+    // Offset of the payload in `record_buffer`, after the record header if any.
+    payload_start: usize,
+    // Id of the last valid record read. Record ids must strictly increase along the log.
+    last_record_id: Option<(u32, u64)>,
+    // Id of the current record, if it carries a record header.
+    record_id: Option<(u32, u64)>,
+}
+
+/// Something a [`RecordReader`] drops while reading.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RecordIssue {
+    /// The frame just read is a `Middle` or `Last` frame that does not follow the beginning of a
+    /// record.
+    OrphanedFrame(FrameType),
+    /// The record being assembled is dropped, as the frame just read starts a new record.
+    IncompleteRecord,
+    /// The checksum of the record just assembled does not match its record header.
+    BadRecordChecksum,
+    /// The id of the record just assembled does not come after the id of the previous record.
+    StaleRecord,
+}
+
+/// Observes what a [`RecordReader`] goes through, for the inspection tools that report what
+/// gets skipped instead of silently reading the valid records.
+pub(crate) trait ReadObserver<R> {
+    /// Called for every frame, valid or not, before it is consumed. Breaking leaves the frame
+    /// unread, as if it was not written yet.
+    fn frame(&mut self, reader: &R, frame: &DecodedFrame) -> ControlFlow<()>;
+
+    /// Called when the frame just read starts a new record.
+    fn record_started(&mut self);
+
+    fn issue(&mut self, issue: RecordIssue);
+}
+
+impl<R> ReadObserver<R> for () {
+    fn frame(&mut self, _reader: &R, _frame: &DecodedFrame) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    fn record_started(&mut self) {}
+
+    fn issue(&mut self, _issue: RecordIssue) {}
 }
 
 impl<R: BlockRead + Unpin> RecordReader<R> {
@@ -22,6 +67,9 @@ impl<R: BlockRead + Unpin> RecordReader<R> {
             frame_reader,
             record_buffer: Vec::with_capacity(10_000),
             within_record: false,
+            payload_start: 0,
+            last_record_id: None,
+            record_id: None,
         }
     }
 
@@ -31,7 +79,74 @@ impl<R: BlockRead + Unpin> RecordReader<R> {
 
     /// Deserialize a record without actually consuming data.
     pub fn record<'a, S: Serializable<'a>>(&'a self) -> Option<S> {
-        S::deserialize(&self.record_buffer)
+        S::deserialize(&self.record_buffer[self.payload_start..])
+    }
+
+    /// Epoch of the last valid record read, if any record carrying a header was read.
+    pub fn last_epoch(&self) -> Option<u32> {
+        self.last_record_id.map(|(epoch, _)| epoch)
+    }
+
+    /// Id of the current record, if it carries a record header.
+    pub(crate) fn record_id(&self) -> Option<(u32, u64)> {
+        self.record_id
+    }
+
+    /// Length of the current record, including its record header.
+    pub(crate) fn record_len(&self) -> usize {
+        self.record_buffer.len()
+    }
+
+    /// Returns true if the reader is in the middle of a record made of several frames.
+    pub(crate) fn is_within_record(&self) -> bool {
+        self.within_record
+    }
+
+    pub(crate) fn read_mut(&mut self) -> &mut R {
+        self.frame_reader.read_mut()
+    }
+
+    /// Skips the rest of the current block, even if more frames could be written in it.
+    ///
+    /// Returns false if there is no next block.
+    pub(crate) fn skip_to_next_block(&mut self) -> io::Result<bool> {
+        self.frame_reader.skip_to_next_block()
+    }
+
+    // Validates the record header of the record that was just assembled.
+    //
+    // Records with a bad checksum were partially written, or assembled from frames of different
+    // records. Records whose id does not come after the id of the previous record are
+    // leftovers from an earlier incarnation of the file.
+    //
+    // Returns false for epoch markers, which carry no payload.
+    fn check_record(
+        &mut self,
+        observer: &mut impl ReadObserver<R>,
+    ) -> Result<bool, ReadRecordError> {
+        let (header, is_epoch_marker) = match RecordHeader::check(&self.record_buffer) {
+            RecordCheck::Valid(header) => (header, false),
+            RecordCheck::EpochMarker(header) => (header, true),
+            RecordCheck::Unchecked => {
+                self.payload_start = 0;
+                self.record_id = None;
+                return Ok(true);
+            }
+            RecordCheck::Corrupted => {
+                observer.issue(RecordIssue::BadRecordChecksum);
+                return Err(ReadRecordError::Corruption);
+            }
+        };
+        if let Some(last_record_id) = self.last_record_id {
+            if header.id() <= last_record_id {
+                observer.issue(RecordIssue::StaleRecord);
+                return Err(ReadRecordError::Corruption);
+            }
+        }
+        self.last_record_id = Some(header.id());
+        self.record_id = Some(header.id());
+        self.payload_start = RECORD_HEADER_LEN;
+        Ok(!is_epoch_marker)
     }
 
     /// Advance cursor and deserialize the next record.
@@ -50,41 +165,78 @@ impl<R: BlockRead + Unpin> RecordReader<R> {
     // Attempts to position the reader to the next record and return
     // true or false whether such a record is available or not.
     pub fn go_next(&mut self) -> Result<bool, ReadRecordError> {
+        self.go_next_observed(&mut ())
+    }
+
+    /// Same as `go_next`, reporting every frame read and everything dropped to `observer`.
+    pub(crate) fn go_next_observed(
+        &mut self,
+        observer: &mut impl ReadObserver<R>,
+    ) -> Result<bool, ReadRecordError> {
         loop {
-            let frame = self.frame_reader.read_frame();
-            match frame {
-                Ok((frame_type, frame_payload)) => {
-                    if frame_type.is_first_frame_of_record() {
-                        self.within_record = true;
-                        self.record_buffer.clear();
-                    }
-                    if self.within_record {
-                        self.record_buffer.extend_from_slice(frame_payload);
-                        if frame_type.is_last_frame_of_record() {
-                            self.within_record = false;
-                            return Ok(true);
-                        }
-                    }
-                }
-                Err(ReadFrameError::Corruption) => {
-                    self.within_record = false;
-                    return Err(ReadRecordError::Corruption);
+            let frame = match self.frame_reader.peek_frame() {
+                Ok(frame) => frame,
+                Err(read_frame_error) => return self.frame_error(read_frame_error),
+            };
+            if observer.frame(self.frame_reader.read(), &frame).is_break() {
+                return Ok(false);
+            }
+            let (frame_type, frame_payload) = match self.frame_reader.consume_frame(&frame) {
+                Ok(frame_type_and_payload) => frame_type_and_payload,
+                Err(read_frame_error) => return self.frame_error(read_frame_error),
+            };
+            if frame_type.is_first_frame_of_record() {
+                if self.within_record {
+                    observer.issue(RecordIssue::IncompleteRecord);
                 }
-                Err(ReadFrameError::IoError(io_err)) => {
-                    self.within_record = false;
-                    return Err(ReadRecordError::IoError(io_err));
+                observer.record_started();
+                self.within_record = true;
+                self.record_buffer.clear();
+            }
+            if !self.within_record {
+                // Blocks padded after a write error end with a `Middle` frame filled with
+                // `0xFF`, which is expected outside of a record.
+                let is_padding = frame_type == FrameType::Middle
+                    && frame_payload.iter().all(|byte| *byte == 0xFF);
+                if !is_padding {
+                    observer.issue(RecordIssue::OrphanedFrame(frame_type));
                 }
-                Err(ReadFrameError::NotAvailable) => {
-                    return Ok(false);
+                continue;
+            }
+            self.record_buffer.extend_from_slice(frame_payload);
+            if frame_type.is_last_frame_of_record() {
+                self.within_record = false;
+                if self.check_record(observer)? {
+                    return Ok(true);
                 }
             }
         }
     }
+
+    fn frame_error(&mut self, read_frame_error: ReadFrameError) -> Result<bool, ReadRecordError> {
+        match read_frame_error {
+            ReadFrameError::Corruption => {
+                self.within_record = false;
+                Err(ReadRecordError::Corruption)
+            }
+            ReadFrameError::IoError(io_err) => {
+                self.within_record = false;
+                Err(ReadRecordError::IoError(io_err))
+            }
+            ReadFrameError::NotAvailable => Ok(false),
+        }
+    }
 }
 
 impl RecordReader<RollingReader> {
     pub fn into_writer(self) -> io::Result<RecordWriter<RollingWriter>> {
+        let epoch = self.last_epoch().map(|epoch| epoch + 1).unwrap_or(1);
         let frame_writer: FrameWriter<RollingWriter> = self.frame_reader.into_writer()?;
-        Ok(RecordWriter::from(frame_writer))
+        let mut record_writer = RecordWriter::with_epoch(frame_writer, epoch);
+        record_writer.write_epoch_marker_before_first_record();
+        // Makes the records read durable, if the previous writer crashed before fsyncing them.
+        // Nothing gets written.
+        record_writer.persist(PersistAction::FlushAndFsync)?;
+        Ok(record_writer)
     }
 }
@@ -1,7 +1,7 @@
 use super::{RecordReader, RecordWriter};
-use crate::block_read_write::ArrayReader;
+use crate::block_read_write::{ArrayReader, VecBlockWriter};
 use crate::error::ReadRecordError;
-use crate::frame::HEADER_LEN;
+use crate::frame::{FrameWriter, HEADER_LEN};
 use crate::{PersistAction, BLOCK_NUM_BYTES};
 
 #[test]
@@ -111,8 +111,8 @@ fn test_behavior_upon_corruption() {
     buffer[1_000] = 3;
     {
         let mut reader = RecordReader::open(ArrayReader::from(&buffer[..]));
-        for record in &records[0..72] {
-            // bug at i=72
+        for record in &records[0..32] {
+            // bug at i=32
             assert_eq!(reader.read_record::<&str>().unwrap(), Some(record.as_str()));
         }
         assert!(matches!(
@@ -121,3 +121,51 @@ fn test_behavior_upon_corruption() {
         ));
     }
 }
+
+fn write_in_memory(epoch: u32, records: &[&str]) -> Vec<u8> {
+    let mut writer =
+        RecordWriter::with_epoch(FrameWriter::create(VecBlockWriter::default()), epoch);
+    for record in records {
+        writer.write_record(*record).unwrap();
+    }
+    writer.persist(PersistAction::Flush).unwrap();
+    writer.into_writer().into()
+}
+
+#[test]
+fn test_stale_records_are_rejected() {
+    let mut buffer = write_in_memory(1, &["aaaa", "bbbb", "cccc"]);
+    let overwrite = write_in_memory(2, &["xxxx"]);
+    // The newer incarnation of the log only rewrote the first record.
+    let record_len = HEADER_LEN + super::header::RECORD_HEADER_LEN + 4;
+    buffer[..record_len].copy_from_slice(&overwrite[..record_len]);
+    let mut reader = RecordReader::open(ArrayReader::from(&buffer[..]));
+    assert_eq!(reader.read_record::<&str>().unwrap(), Some("xxxx"));
+    assert!(matches!(
+        reader.read_record::<&str>(),
+        Err(ReadRecordError::Corruption)
+    ));
+    assert!(matches!(
+        reader.read_record::<&str>(),
+        Err(ReadRecordError::Corruption)
+    ));
+    assert_eq!(reader.read_record::<&str>().unwrap(), None);
+    assert_eq!(reader.last_epoch(), Some(2));
+}
+
+#[test]
+fn test_record_assembled_from_different_writes_is_rejected() {
+    let long_record_a = "A".repeat(BLOCK_NUM_BYTES);
+    let long_record_b = "B".repeat(BLOCK_NUM_BYTES);
+    let mut buffer = write_in_memory(1, &[&long_record_a, "hello"]);
+    let overwrite = write_in_memory(2, &[&long_record_b]);
+    // Every frame is valid, but the first frame comes from one write and the last frame from
+    // another.
+    buffer[BLOCK_NUM_BYTES..2 * BLOCK_NUM_BYTES]
+        .copy_from_slice(&overwrite[BLOCK_NUM_BYTES..2 * BLOCK_NUM_BYTES]);
+    let mut reader = RecordReader::open(ArrayReader::from(&buffer[..]));
+    assert!(matches!(
+        reader.read_record::<&str>(),
+        Err(ReadRecordError::Corruption)
+    ));
+}
@@ -1,5 +1,9 @@
 use std::io;
+use std::ops::RangeInclusive;
 
+use bytes::Buf;
+
+use super::header::RecordHeader;
 use crate::block_read_write::VecBlockWriter;
 use crate::frame::{FrameType, FrameWriter};
 use crate::rolling::{Directory, FileNumber, RollingWriter};
@@ -8,6 +12,13 @@ use crate::{BlockWrite, PersistAction, Serializable};
 pub struct RecordWriter<W> {
     frame_writer: FrameWriter<W>,
     buffer: Vec<u8>,
+    epoch: u32,
+    next_sequence: u64,
+    // Whether records were written since the last flush, and since the last fsync.
+    needs_flush: bool,
+    needs_fsync: bool,
+    // Whether an epoch marker must be made durable before the next record.
+    needs_epoch_marker: bool,
 }
 
 fn frame_type(is_first_frame: bool, is_last_frame: bool) -> FrameType {
@@ -19,13 +30,37 @@ fn frame_type(is_first_frame: bool, is_last_frame: bool) -> FrameType {
     }
 }
 
-impl<W: BlockWrite + Unpin> From<FrameWriter<W>> for RecordWriter<W> {
-    fn from(frame_writer: FrameWriter<W>) -> Self {
+impl<W: BlockWrite + Unpin> RecordWriter<W> {
+    /// Creates a writer whose records are stamped with the given epoch.
+    ///
+    /// The epoch must be greater than the epoch of any record already present in the log.
+    pub fn with_epoch(frame_writer: FrameWriter<W>, epoch: u32) -> Self {
         RecordWriter {
             frame_writer,
             buffer: Vec::with_capacity(10_000),
+            epoch,
+            next_sequence: 0,
+            needs_flush: false,
+            needs_fsync: false,
+            needs_epoch_marker: false,
         }
     }
+
+    /// Makes the writer write and fsync an epoch marker before its first record.
+    ///
+    /// The epoch must be on disk before any record of this writer: the next writer would
+    /// otherwise reuse it, and its records would be accepted even if leftovers of this writer
+    /// with greater sequence numbers followed them. A writer that writes nothing does not need
+    /// it, and does not touch the log.
+    pub fn write_epoch_marker_before_first_record(&mut self) {
+        self.needs_epoch_marker = true;
+    }
+}
+
+impl<W: BlockWrite + Unpin> From<FrameWriter<W>> for RecordWriter<W> {
+    fn from(frame_writer: FrameWriter<W>) -> Self {
+        RecordWriter::with_epoch(frame_writer, 1)
+    }
 }
 
 impl<W: BlockWrite + Unpin> RecordWriter<W> {
@@ -46,21 +81,50 @@ impl<W: BlockWrite + Unpin> RecordWriter<W> {
     /// by a writer level buffer, or an application buffer,
     /// or could not be flushed to disk yet by the OS.
     pub fn write_record<'a>(&mut self, record: impl Serializable<'a>) -> io::Result<u64> {
-        let mut is_first_frame = true;
-        let mut num_bytes_written: u64 = 0;
-
+        let mut num_bytes_written = 0;
+        if self.needs_epoch_marker {
+            num_bytes_written += self.write_epoch_marker()?;
+            self.persist(PersistAction::FlushAndFsync)?;
+            self.needs_epoch_marker = false;
+        }
         self.buffer.clear();
         record.serialize(&mut self.buffer);
-        let mut payload = &self.buffer[..];
+        let record_header_bytes = self.next_record_header().serialize(&self.buffer);
+        Ok(num_bytes_written + self.write_frames(&record_header_bytes)?)
+    }
+
+    /// Writes an epoch marker, which readers skip. It records the epoch of the writer in the
+    /// log.
+    fn write_epoch_marker(&mut self) -> io::Result<u64> {
+        self.buffer.clear();
+        let record_header_bytes = self.next_record_header().serialize_epoch_marker();
+        self.write_frames(&record_header_bytes)
+    }
+
+    fn next_record_header(&mut self) -> RecordHeader {
+        let record_header = RecordHeader {
+            epoch: self.epoch,
+            sequence: self.next_sequence,
+        };
+        self.next_sequence += 1;
+        record_header
+    }
+
+    // Writes the record header followed by the content of the buffer, split into frames.
+    fn write_frames(&mut self, record_header_bytes: &[u8]) -> io::Result<u64> {
+        let mut is_first_frame = true;
+        let mut num_bytes_written: u64 = 0;
+        self.needs_flush = true;
+        self.needs_fsync = true;
+        let mut payload = record_header_bytes.chain(&self.buffer[..]);
 
         loop {
             let frame_payload_len = self
                 .frame_writer
                 .max_writable_frame_length()
-                .min(payload.len());
-            let frame_payload = &payload[..frame_payload_len];
-            payload = &payload[frame_payload_len..];
-            let is_last_frame = payload.is_empty();
+                .min(payload.remaining());
+            let is_last_frame = frame_payload_len == payload.remaining();
+            let frame_payload = (&mut payload).take(frame_payload_len);
             let frame_type = frame_type(is_first_frame, is_last_frame);
             num_bytes_written += self.frame_writer.write_frame(frame_type, frame_payload)? as u64;
             is_first_frame = false;
@@ -74,7 +138,22 @@ impl<W: BlockWrite + Unpin> RecordWriter<W> {
 
     /// Persist the data to disk, according to the persist_action.
     pub fn persist(&mut self, persist_action: PersistAction) -> io::Result<()> {
-        self.frame_writer.persist(persist_action)
+        self.frame_writer.persist(persist_action)?;
+        self.needs_flush = false;
+        if persist_action.is_fsync() {
+            self.needs_fsync = false;
+        }
+        Ok(())
+    }
+
+    /// Returns true if records were written since they were last persisted with the given
+    /// action.
+    pub fn needs_persist(&self, persist_action: PersistAction) -> bool {
+        if persist_action.is_fsync() {
+            self.needs_fsync
+        } else {
+            self.needs_flush
+        }
     }
 
     pub fn get_underlying_wrt(&self) -> &W {
@@ -87,6 +166,32 @@ impl RecordWriter<RollingWriter> {
         self.frame_writer.directory()
     }
 
+    /// Brings the writer back to a consistent state after an io error.
+    ///
+    /// Whatever is buffered is flushed, the write offset is realigned on the actual end of the
+    /// file, and the current block is padded: a record that was only partially written will be
+    /// ignored when reading the log back.
+    pub fn recover_from_io_error(&mut self) -> io::Result<()> {
+        self.frame_writer.resync_offset()?;
+        self.frame_writer.pad_block()?;
+        self.frame_writer.persist(PersistAction::Flush)
+    }
+
+    #[cfg(test)]
+    pub fn simulate_disk_full(&mut self, num_bytes_available: Option<usize>) {
+        self.frame_writer.simulate_disk_full(num_bytes_available)
+    }
+
+    /// Notes the records about to be written, to index them on the current wal file.
+    pub fn index_records(&mut self, queue: &str, positions: RangeInclusive<u64>) {
+        self.frame_writer.index_records(queue, positions)
+    }
+
+    /// Indexes the records noted by [`Self::index_records`] if they were written.
+    pub fn commit_indexed_records(&mut self, written: bool) {
+        self.frame_writer.commit_indexed_records(written)
+    }
+
     pub fn current_file(&mut self) -> &FileNumber {
         self.get_underlying_wrt().current_file()
     }
@@ -0,0 +1,105 @@
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use tracing::info;
+
+use super::directory::{filename_to_position, remove_file_if_exists};
+use super::file_index::index_filepath;
+
+/// Moves a file to the archive directory, keeping its file name.
+pub(crate) fn archive_file(filepath: &Path, archive_dir: &Path) -> io::Result<()> {
+    std::fs::create_dir_all(archive_dir)?;
+    let Some(file_name) = filepath.file_name() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "cannot archive a path without file name",
+        ));
+    };
+    let archived_filepath = archive_dir.join(file_name);
+    info!(file=%filepath.display(), archive=%archived_filepath.display(), "gc archive file");
+    match std::fs::rename(filepath, &archived_filepath) {
+        Ok(()) => Ok(()),
+        // The archive is on a different filesystem, in which case renaming is not possible.
+        Err(io_err) if is_cross_device(&io_err) => copy_to_archive(filepath, &archived_filepath),
+        Err(io_err) => Err(io_err),
+    }
+}
+
+fn is_cross_device(io_err: &io::Error) -> bool {
+    #[cfg(unix)]
+    const CROSS_DEVICE_ERROR: Option<i32> = Some(18); // EXDEV
+    #[cfg(windows)]
+    const CROSS_DEVICE_ERROR: Option<i32> = Some(17); // ERROR_NOT_SAME_DEVICE
+    #[cfg(not(any(unix, windows)))]
+    const CROSS_DEVICE_ERROR: Option<i32> = None;
+    CROSS_DEVICE_ERROR.is_some() && io_err.raw_os_error() == CROSS_DEVICE_ERROR
+}
+
+/// Copies a file to the archive, then removes it.
+///
+/// The copy is written under a temporary name and synced before being renamed, so that the
+/// archive never holds a partial copy under the name of a wal file.
+pub(super) fn copy_to_archive(filepath: &Path, archived_filepath: &Path) -> io::Result<()> {
+    let mut tmp_file_name = archived_filepath.file_name().unwrap_or_default().to_owned();
+    tmp_file_name.push(".tmp");
+    let tmp_filepath = archived_filepath.with_file_name(tmp_file_name);
+    let copy_res = std::fs::copy(filepath, &tmp_filepath)
+        .and_then(|_| File::open(&tmp_filepath)?.sync_all())
+        .and_then(|_| std::fs::rename(&tmp_filepath, archived_filepath));
+    if let Err(io_err) = copy_res {
+        let _ = remove_file_if_exists(&tmp_filepath);
+        return Err(io_err);
+    }
+    std::fs::remove_file(filepath)
+}
+
+/// Removes the oldest archived wal files until the archive holds at most `max_num_bytes`, and
+/// none of its files were last modified more than `max_age` ago.
+///
+/// The sidecar index of a removed wal file is removed with it.
+pub(crate) fn enforce_archive_limits(
+    archive_dir: &Path,
+    max_num_bytes: u64,
+    max_age: Duration,
+) -> io::Result<()> {
+    let mut archived_files: Vec<(u64, PathBuf, u64, SystemTime)> = Vec::new();
+    for dir_entry_res in std::fs::read_dir(archive_dir)? {
+        let dir_entry = dir_entry_res?;
+        let Some(file_name) = dir_entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let Some(file_number) = filename_to_position(&file_name) else {
+            continue;
+        };
+        let metadata = dir_entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        archived_files.push((
+            file_number,
+            dir_entry.path(),
+            metadata.len(),
+            metadata.modified()?,
+        ));
+    }
+    archived_files.sort_unstable_by_key(|(file_number, ..)| *file_number);
+
+    let mut total_num_bytes: u64 = archived_files.iter().map(|(_, _, len, _)| len).sum();
+    let now = SystemTime::now();
+    for (file_number, filepath, len, modified) in archived_files {
+        let too_old = now
+            .duration_since(modified)
+            .map(|age| age > max_age)
+            .unwrap_or(false);
+        if total_num_bytes <= max_num_bytes && !too_old {
+            continue;
+        }
+        info!(file=%filepath.display(), "remove archived file");
+        std::fs::remove_file(&filepath)?;
+        remove_file_if_exists(&index_filepath(archive_dir, file_number))?;
+        total_num_bytes -= len;
+    }
+    Ok(())
+}
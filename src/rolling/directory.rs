@@ -1,19 +1,40 @@
+use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
 use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
-use tracing::info;
+use tracing::{info, warn};
 
-use super::{FileNumber, FileTracker};
-use crate::rolling::{FILE_NUM_BYTES, FRAME_NUM_BYTES};
-use crate::{BlockRead, BlockWrite, PersistAction, BLOCK_NUM_BYTES};
+use super::archive::{archive_file, enforce_archive_limits};
+use super::file_index::index_filepath;
+use super::{FileIndex, FileNumber, FileTracker};
+use crate::listener::{LogListener, NoopListener};
+use crate::rolling::{FILE_NUM_BYTES, FRAME_NUM_BYTES, NUM_BLOCKS_PER_FILE};
+use crate::stats::IoStatsRecorder;
+use crate::{BlockRead, BlockWrite, GcAction, PersistAction, BLOCK_NUM_BYTES};
+
+#[cfg(not(test))]
+type WalWriter = BufWriter<File>;
+
+// Tests can make writes fail as if the disk was full.
+#[cfg(test)]
+type WalWriter = super::disk_full::DiskFullInjector<BufWriter<File>>;
 
 pub struct Directory {
     dir: PathBuf,
     pub(crate) files: FileTracker,
+    gc_action: GcAction,
+    pub(crate) stats: Arc<IoStatsRecorder>,
+    listener: Arc<dyn LogListener>,
+    // Oldest file, and since when it could be GCed if no queue held records in it: when the
+    // writer rolled past it, or when the files before it were GCed.
+    oldest_file_collectable_since: Option<(u64, SystemTime)>,
 }
 
-fn filename_to_position(file_name: &str) -> Option<u64> {
+pub(crate) fn filename_to_position(file_name: &str) -> Option<u64> {
     if file_name.len() != 24 {
         return None;
     }
@@ -31,6 +52,14 @@ pub(crate) fn filepath(dir: &Path, file_number: &FileNumber) -> PathBuf {
     dir.join(file_number.filename())
 }
 
+pub(crate) fn remove_file_if_exists(filepath: &Path) -> io::Result<()> {
+    match std::fs::remove_file(filepath) {
+        Ok(()) => Ok(()),
+        Err(io_err) if io_err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(io_err) => Err(io_err),
+    }
+}
+
 fn create_file(dir_path: &Path, file_number: &FileNumber) -> io::Result<File> {
     let new_filepath = filepath(dir_path, file_number);
     let mut file = OpenOptions::new()
@@ -46,6 +75,7 @@ impl Directory {
     /// Open a `Directory`, or create a new, empty, one. `dir_path` must exist and be a directory.
     pub fn open(dir_path: &Path) -> io::Result<Directory> {
         let mut file_numbers: Vec<u64> = Default::default();
+        let mut file_creation_times: HashMap<u64, SystemTime> = HashMap::new();
         for dir_entry_res in std::fs::read_dir(dir_path)? {
             let dir_entry = dir_entry_res?;
             if !dir_entry.file_type()?.is_file() {
@@ -58,19 +88,36 @@ impl Directory {
             };
             if let Some(seq_number) = filename_to_position(&file_name) {
                 file_numbers.push(seq_number);
+                let metadata = dir_entry.metadata()?;
+                let creation_time = metadata
+                    .created()
+                    .or_else(|_| metadata.modified())
+                    .unwrap_or_else(|_| SystemTime::now());
+                file_creation_times.insert(seq_number, creation_time);
             }
         }
         let files = if let Some(files) = FileTracker::from_file_numbers(file_numbers) {
             files
         } else {
             let files = FileTracker::new();
-            let file_number = files.first();
-            create_file(dir_path, file_number)?;
+            create_file(dir_path, files.first())?;
             files
         };
+        // The writer rolled past the oldest file when it created the next one.
+        let oldest_file_collectable_since = files.next(files.first()).map(|next_file| {
+            let collectable_since = file_creation_times
+                .get(&next_file.file_number())
+                .copied()
+                .unwrap_or_else(SystemTime::now);
+            (files.first().file_number(), collectable_since)
+        });
         Ok(Directory {
             dir: dir_path.to_path_buf(),
             files,
+            gc_action: GcAction::Delete,
+            stats: Arc::default(),
+            listener: Arc::new(NoopListener),
+            oldest_file_collectable_since,
         })
     }
 
@@ -84,18 +131,94 @@ impl Directory {
         self.files.count() >= 2 && self.files.first().can_be_deleted()
     }
 
+    /// Sets what happens to wal files when they get GCed.
+    pub(crate) fn set_gc_action(&mut self, gc_action: GcAction) {
+        self.gc_action = gc_action;
+    }
+
+    /// Returns for how long the oldest file could have been GCed, if no queue held records in
+    /// it. Returns `None` if it is the file being written.
+    pub fn oldest_file_blocked_for(&self) -> Option<Duration> {
+        let (_, collectable_since) = self.oldest_file_collectable_since?;
+        Some(
+            SystemTime::now()
+                .duration_since(collectable_since)
+                .unwrap_or_default(),
+        )
+    }
+
+    // Files only change when the writer rolls, and when they are GCed.
+    fn update_oldest_file_collectable_since(&mut self) {
+        if self.files.count() < 2 {
+            self.oldest_file_collectable_since = None;
+            return;
+        }
+        let oldest_file_number = self.files.first().file_number();
+        let tracked_file_number = self
+            .oldest_file_collectable_since
+            .map(|(file_number, _)| file_number);
+        if tracked_file_number != Some(oldest_file_number) {
+            self.oldest_file_collectable_since = Some((oldest_file_number, SystemTime::now()));
+        }
+    }
+
+    pub(crate) fn set_listener(&mut self, listener: Arc<dyn LogListener>) {
+        self.listener = listener;
+    }
+
     /// Delete FileNumbers and the associated wal files no longer used.
     ///
-    /// We never delete the last file.
+    /// Depending on the `GcAction`, files are archived or handed to a callback before being
+    /// removed. We never delete the last file.
     pub(crate) fn gc(&mut self) -> io::Result<()> {
+        let mut has_archived_files = false;
         while let Some(file) = self.files.take_first_unused() {
             let filepath = filepath(&self.dir, &file);
-            info!(file=%filepath.display(), "gc remove file");
-            std::fs::remove_file(&filepath)?;
+            let index_filepath = index_filepath(&self.dir, file.file_number());
+            match &self.gc_action {
+                GcAction::Delete => {
+                    info!(file=%filepath.display(), "gc remove file");
+                    std::fs::remove_file(&filepath)?;
+                    remove_file_if_exists(&index_filepath)?;
+                }
+                GcAction::Archive { directory, .. } => {
+                    archive_file(&filepath, directory)?;
+                    if index_filepath.exists() {
+                        archive_file(&index_filepath, directory)?;
+                    }
+                    has_archived_files = true;
+                }
+                GcAction::Callback(callback) => {
+                    callback(&filepath);
+                    info!(file=%filepath.display(), "gc remove file");
+                    std::fs::remove_file(&filepath)?;
+                    remove_file_if_exists(&index_filepath)?;
+                }
+            }
+            self.stats.record_gced_file();
+            self.listener.on_file_gced(file.file_number(), &filepath);
+        }
+        self.update_oldest_file_collectable_since();
+        if has_archived_files {
+            if let GcAction::Archive {
+                directory,
+                max_num_bytes,
+                max_age,
+            } = &self.gc_action
+            {
+                enforce_archive_limits(directory, *max_num_bytes, *max_age)?;
+            }
         }
         Ok(())
     }
 
+    /// Writes the index of a wal file next to it.
+    fn write_index(&self, file_number: &FileNumber, index: &FileIndex) -> io::Result<()> {
+        let mut buffer = Vec::new();
+        index.serialize(&mut buffer);
+        std::fs::write(index_filepath(&self.dir, file_number.file_number()), buffer)
+    }
+
     /// Open the wal file with the provided FileNumber.
     pub fn open_file(&self, file_number: &FileNumber) -> io::Result<File> {
         let filepath = filepath(&self.dir, file_number);
@@ -151,9 +274,11 @@ impl RollingReader {
         let offset = self.block_id * crate::BLOCK_NUM_BYTES;
         self.file.seek(SeekFrom::Start(offset as u64))?;
         Ok(RollingWriter {
-            file: BufWriter::with_capacity(FRAME_NUM_BYTES, self.file),
+            file: WalWriter::from(BufWriter::with_capacity(FRAME_NUM_BYTES, self.file)),
             offset,
             file_number: self.file_number.clone(),
+            index: FileIndex::starting_at_block(self.block_id),
+            pending_index_entry: None,
             directory: self.directory,
         })
     }
@@ -207,9 +332,13 @@ impl BlockRead for RollingReader {
 }
 
 pub struct RollingWriter {
-    file: BufWriter<File>,
+    file: WalWriter,
     offset: usize,
     file_number: FileNumber,
+    // Index of the records written to the current file, persisted when rolling to the next one.
+    index: FileIndex,
+    // Records being written, added to the index once the write succeeded.
+    pending_index_entry: Option<(String, RangeInclusive<u64>, u32)>,
     pub(crate) directory: Directory,
 }
 
@@ -225,8 +354,57 @@ impl RollingWriter {
         &self.file_number
     }
 
+    /// Flushes the buffer and realigns the write offset on the actual position in the file.
+    ///
+    /// After an io error, part of a write may or may not have reached the file.
+    pub fn resync_offset(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.offset = self.file.get_mut().stream_position()? as usize;
+        Ok(())
+    }
+
+    /// Makes writes fail as if the disk was full, once `num_bytes_available` more bytes were
+    /// written. `None` lifts the limit.
+    #[cfg(test)]
+    pub fn simulate_disk_full(&mut self, num_bytes_available: Option<usize>) {
+        self.file.set_num_bytes_available(num_bytes_available);
+    }
+
+    /// Notes the records about to be written, to index them on the current file.
+    ///
+    /// This must be called before writing the records, and followed by
+    /// [`Self::commit_indexed_records`] once the write is over.
+    pub fn index_records(&mut self, queue: &str, positions: RangeInclusive<u64>) {
+        // The record may start on the next block, or even on the next file, if the frame header
+        // does not fit in the current block. Indexing it on the current block is conservative:
+        // reading from there will reach it.
+        let block_id = (self.offset / BLOCK_NUM_BYTES).min(NUM_BLOCKS_PER_FILE - 1);
+        self.pending_index_entry = Some((queue.to_string(), positions, block_id as u32));
+    }
+
+    /// Adds the records noted by [`Self::index_records`] to the index if they were written, or
+    /// forgets them otherwise.
+    ///
+    /// Records whose write rolled the file were already indexed on the file they started in.
+    pub fn commit_indexed_records(&mut self, written: bool) {
+        if let Some((queue, positions, block_id)) = self.pending_index_entry.take() {
+            if written {
+                self.index.record(&queue, positions, block_id);
+            }
+        }
+    }
+
     pub fn size(&self) -> usize {
-        self.directory.files.count() * FILE_NUM_BYTES
+        self.num_files() * FILE_NUM_BYTES
+    }
+
+    pub fn num_files(&self) -> usize {
+        self.directory.files.count()
+    }
+
+    /// Returns the disk space used by the files numbered lower than `file_number`.
+    pub fn size_before(&self, file_number: u64) -> usize {
+        self.directory.files.count_before(file_number) * FILE_NUM_BYTES
     }
 
     #[cfg(test)]
@@ -244,41 +422,66 @@ impl BlockWrite for RollingWriter {
         }
         assert!(buf.len() <= self.num_bytes_remaining_in_block());
         if self.offset + buf.len() > FILE_NUM_BYTES {
+            let start = Instant::now();
             self.file.flush()?;
             self.file.get_ref().sync_data()?;
             self.directory.sync_directory()?;
+            // The records being written start in this file.
+            self.commit_indexed_records(true);
+            // The index is only an optimization: the wal file can be scanned without it.
+            if let Err(io_err) = self.directory.write_index(&self.file_number, &self.index) {
+                let file_number = self.file_number.file_number();
+                warn!(file_number, error=?io_err, "failed to write wal file index");
+            }
 
-            let (file_number, file) =
-                if let Some(next_file_number) = self.directory.files.next(&self.file_number) {
-                    let file = self.directory.open_file(&next_file_number)?;
-                    (next_file_number, file)
-                } else {
-                    let next_file_number = self.directory.files.inc(&self.file_number);
-                    let file = create_file(&self.directory.dir, &next_file_number)?;
-                    (next_file_number, file)
-                };
+            let previous_file_number = self.file_number.file_number();
+            let file_number = self.directory.files.inc(&self.file_number);
+            let file = match self.directory.open_file(&file_number) {
+                Ok(file) => file,
+                // The file is either new, or its creation failed during a previous attempt.
+                Err(io_err) if io_err.kind() == io::ErrorKind::NotFound => {
+                    create_file(&self.directory.dir, &file_number)?
+                }
+                Err(io_err) => return Err(io_err),
+            };
 
-            self.file = BufWriter::with_capacity(FRAME_NUM_BYTES, file);
+            self.directory.update_oldest_file_collectable_since();
+            #[cfg(test)]
+            let num_bytes_available = self.file.num_bytes_available();
+            self.file = WalWriter::from(BufWriter::with_capacity(FRAME_NUM_BYTES, file));
+            #[cfg(test)]
+            self.file.set_num_bytes_available(num_bytes_available);
             self.file_number = file_number;
             self.offset = 0;
+            self.index = FileIndex::default();
+            self.directory.stats.record_roll(start.elapsed());
+            self.directory
+                .listener
+                .on_file_rolled(previous_file_number, self.file_number.file_number());
         }
         self.offset += buf.len();
         self.file.write_all(buf)?;
+        self.directory.stats.record_write(buf.len());
         Ok(())
     }
 
     fn persist(&mut self, persist_action: PersistAction) -> io::Result<()> {
+        let start = Instant::now();
         match persist_action {
             PersistAction::FlushAndFsync => {
                 self.file.flush()?;
                 self.file.get_ref().sync_data()?;
-                self.directory.sync_directory()
+                self.directory.sync_directory()?;
             }
             PersistAction::Flush => {
                 // This will flush the buffer of the BufWriter to the underlying OS.
-                self.file.flush()
+                self.file.flush()?;
             }
         }
+        self.directory
+            .stats
+            .record_persist(persist_action, start.elapsed());
+        Ok(())
     }
 
     fn num_bytes_remaining_in_block(&self) -> usize {
@@ -0,0 +1,71 @@
+use std::io::{self, Write};
+use std::ops::{Deref, DerefMut};
+
+/// Wraps the writer of the wal files in tests, to make writes fail as if the disk was full.
+pub(crate) struct DiskFullInjector<W> {
+    inner: W,
+    // Number of bytes that can still be written before the disk is full, if it is limited.
+    num_bytes_available: Option<usize>,
+}
+
+impl<W> From<W> for DiskFullInjector<W> {
+    fn from(inner: W) -> Self {
+        DiskFullInjector {
+            inner,
+            num_bytes_available: None,
+        }
+    }
+}
+
+impl<W> DiskFullInjector<W> {
+    /// Makes writes fail once `num_bytes_available` more bytes were written, or never fail if
+    /// `None`.
+    pub fn set_num_bytes_available(&mut self, num_bytes_available: Option<usize>) {
+        self.num_bytes_available = num_bytes_available;
+    }
+
+    pub fn num_bytes_available(&self) -> Option<usize> {
+        self.num_bytes_available
+    }
+}
+
+fn disk_full_error() -> io::Error {
+    io::Error::from_raw_os_error(28) // ENOSPC
+}
+
+impl<W: Write> Write for DiskFullInjector<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(num_bytes_available) = self.num_bytes_available.as_mut() else {
+            return self.inner.write(buf);
+        };
+        if *num_bytes_available == 0 && !buf.is_empty() {
+            return Err(disk_full_error());
+        }
+        // Writes end up being torn when the disk gets full.
+        let num_bytes_to_write = buf.len().min(*num_bytes_available);
+        let num_bytes_written = self.inner.write(&buf[..num_bytes_to_write])?;
+        *num_bytes_available -= num_bytes_written;
+        Ok(num_bytes_written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.num_bytes_available == Some(0) {
+            return Err(disk_full_error());
+        }
+        self.inner.flush()
+    }
+}
+
+impl<W> Deref for DiskFullInjector<W> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        &self.inner
+    }
+}
+
+impl<W> DerefMut for DiskFullInjector<W> {
+    fn deref_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+/// Position and block range a queue occupies within a single wal file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueueFileRange {
+    /// Position of the first record of the queue appended in this file.
+    pub first_position: u64,
+    /// Position of the last record of the queue appended in this file.
+    pub last_position: u64,
+    /// Block from which a sequential read reaches the first record of the queue.
+    pub first_block_id: u32,
+    /// Block from which a sequential read reaches the last record of the queue.
+    pub last_block_id: u32,
+}
+
+/// Index of the queue positions stored in a wal file.
+///
+/// It is written as a sidecar file (`wal-<file number>.idx`) when the writer rolls to the next
+/// file, so that old positions can be located without scanning the log from its first file.
+///
+/// Block ids are conservative: a record may start a bit later than its indexed block (possibly
+/// in the next file), but never before it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileIndex {
+    // Blocks before this one were written before the writer was (re)opened, and are not
+    // covered by the index.
+    first_indexed_block: u32,
+    queues: BTreeMap<String, QueueFileRange>,
+}
+
+const QUEUE_ENTRY_LEN: usize = 8 + 8 + 4 + 4;
+
+pub(crate) fn index_filepath(dir: &Path, file_number: u64) -> PathBuf {
+    dir.join(format!("wal-{file_number:020}.idx"))
+}
+
+impl FileIndex {
+    pub(crate) fn starting_at_block(first_indexed_block: usize) -> Self {
+        FileIndex {
+            first_indexed_block: first_indexed_block as u32,
+            queues: BTreeMap::new(),
+        }
+    }
+
+    /// Loads the index of the wal file `file_number` stored in `dir_path`.
+    ///
+    /// Returns `None` if there is no index for that file (it is still being written, or was
+    /// written by a version of mrecordlog without indexes), or if the index is corrupted.
+    pub fn load(dir_path: &Path, file_number: u64) -> io::Result<Option<FileIndex>> {
+        match std::fs::read(index_filepath(dir_path, file_number)) {
+            Ok(buffer) => Ok(FileIndex::deserialize(&buffer)),
+            Err(io_err) if io_err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(io_err) => Err(io_err),
+        }
+    }
+
+    /// First block covered by the index. Records stored before that block are unknown to the
+    /// index.
+    pub fn first_indexed_block(&self) -> u32 {
+        self.first_indexed_block
+    }
+
+    /// Returns the range a queue occupies in the file, if it has any record in it.
+    pub fn get(&self, queue: &str) -> Option<&QueueFileRange> {
+        self.queues.get(queue)
+    }
+
+    pub fn queues(&self) -> impl Iterator<Item = (&str, &QueueFileRange)> {
+        self.queues
+            .iter()
+            .map(|(queue, range)| (queue.as_str(), range))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queues.is_empty()
+    }
+
+    pub(crate) fn record(&mut self, queue: &str, positions: RangeInclusive<u64>, block_id: u32) {
+        if let Some(range) = self.queues.get_mut(queue) {
+            range.last_position = *positions.end();
+            range.last_block_id = block_id;
+        } else {
+            self.queues.insert(
+                queue.to_string(),
+                QueueFileRange {
+                    first_position: *positions.start(),
+                    last_position: *positions.end(),
+                    first_block_id: block_id,
+                    last_block_id: block_id,
+                },
+            );
+        }
+    }
+
+    /// Layout: `<u32 first indexed block><u32 num queues>`, then for every queue
+    /// `<u16 queue len><queue><u64 first pos><u64 last pos><u32 first block><u32 last block>`,
+    /// and finally a crc32 of everything that precedes it. Integers are little endian.
+    pub(crate) fn serialize(&self, buffer: &mut Vec<u8>) {
+        buffer.clear();
+        buffer.extend_from_slice(&self.first_indexed_block.to_le_bytes());
+        buffer.extend_from_slice(&(self.queues.len() as u32).to_le_bytes());
+        for (queue, range) in &self.queues {
+            assert!(queue.len() <= u16::MAX as usize);
+            buffer.extend_from_slice(&(queue.len() as u16).to_le_bytes());
+            buffer.extend_from_slice(queue.as_bytes());
+            buffer.extend_from_slice(&range.first_position.to_le_bytes());
+            buffer.extend_from_slice(&range.last_position.to_le_bytes());
+            buffer.extend_from_slice(&range.first_block_id.to_le_bytes());
+            buffer.extend_from_slice(&range.last_block_id.to_le_bytes());
+        }
+        let checksum = crc32fast::hash(buffer);
+        buffer.extend_from_slice(&checksum.to_le_bytes());
+    }
+
+    pub(crate) fn deserialize(buffer: &[u8]) -> Option<FileIndex> {
+        if buffer.len() < 12 {
+            return None;
+        }
+        let (body, checksum) = buffer.split_at(buffer.len() - 4);
+        if crc32fast::hash(body) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+            return None;
+        }
+        let first_indexed_block = u32::from_le_bytes(body[0..4].try_into().unwrap());
+        let num_queues = u32::from_le_bytes(body[4..8].try_into().unwrap());
+        let mut body = &body[8..];
+        let mut queues = BTreeMap::new();
+        for _ in 0..num_queues {
+            if body.len() < 2 {
+                return None;
+            }
+            let queue_len = u16::from_le_bytes(body[0..2].try_into().unwrap()) as usize;
+            body = &body[2..];
+            if body.len() < queue_len + QUEUE_ENTRY_LEN {
+                return None;
+            }
+            let queue = std::str::from_utf8(&body[..queue_len]).ok()?.to_string();
+            let entry = &body[queue_len..][..QUEUE_ENTRY_LEN];
+            let range = QueueFileRange {
+                first_position: u64::from_le_bytes(entry[0..8].try_into().unwrap()),
+                last_position: u64::from_le_bytes(entry[8..16].try_into().unwrap()),
+                first_block_id: u32::from_le_bytes(entry[16..20].try_into().unwrap()),
+                last_block_id: u32::from_le_bytes(entry[20..24].try_into().unwrap()),
+            };
+            queues.insert(queue, range);
+            body = &body[queue_len + QUEUE_ENTRY_LEN..];
+        }
+        if !body.is_empty() {
+            return None;
+        }
+        Some(FileIndex {
+            first_indexed_block,
+            queues,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_index_record() {
+        let mut index = FileIndex::default();
+        index.record("q1", 0..=3, 0);
+        index.record("q2", 10..=10, 1);
+        index.record("q1", 4..=7, 2);
+        assert_eq!(
+            index.get("q1"),
+            Some(&QueueFileRange {
+                first_position: 0,
+                last_position: 7,
+                first_block_id: 0,
+                last_block_id: 2,
+            })
+        );
+        assert_eq!(
+            index.get("q2"),
+            Some(&QueueFileRange {
+                first_position: 10,
+                last_position: 10,
+                first_block_id: 1,
+                last_block_id: 1,
+            })
+        );
+        assert_eq!(index.get("q3"), None);
+    }
+
+    #[test]
+    fn test_file_index_serialize_deserialize() {
+        let mut index = FileIndex::starting_at_block(2);
+        index.record("q1", 0..=3, 2);
+        index.record("q2", 10..=10, 3);
+        let mut buffer = Vec::new();
+        index.serialize(&mut buffer);
+        assert_eq!(FileIndex::deserialize(&buffer), Some(index));
+    }
+
+    #[test]
+    fn test_file_index_deserialize_corrupted() {
+        let mut index = FileIndex::default();
+        index.record("q1", 0..=3, 0);
+        let mut buffer = Vec::new();
+        index.serialize(&mut buffer);
+        for num_truncated_bytes in 1..buffer.len() {
+            assert_eq!(
+                FileIndex::deserialize(&buffer[..buffer.len() - num_truncated_bytes]),
+                None
+            );
+        }
+        buffer[7] ^= 1;
+        assert_eq!(FileIndex::deserialize(&buffer), None);
+    }
+}
@@ -77,6 +77,14 @@ impl FileTracker {
     pub fn count(&self) -> usize {
         self.files.len()
     }
+
+    /// Return the number of files tracked whose number is lower than `file_number`.
+    pub fn count_before(&self, file_number: u64) -> usize {
+        self.files
+            .iter()
+            .take_while(|file| file.file_number() < file_number)
+            .count()
+    }
 }
 
 #[derive(Clone, Default, Debug, Ord, PartialOrd, Eq, PartialEq)]
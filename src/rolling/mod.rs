@@ -1,17 +1,23 @@
+mod archive;
 mod directory;
+#[cfg(test)]
+mod disk_full;
+mod file_index;
 mod file_number;
 
+pub(crate) use self::directory::filename_to_position;
 pub use self::directory::{Directory, RollingReader, RollingWriter};
+pub use self::file_index::{FileIndex, QueueFileRange};
 pub use self::file_number::{FileNumber, FileTracker};
 
 const FRAME_NUM_BYTES: usize = 1 << 15;
 
 #[cfg(not(test))]
-const NUM_BLOCKS_PER_FILE: usize = 1 << 12;
+pub(crate) const NUM_BLOCKS_PER_FILE: usize = 1 << 12;
 
 #[cfg(test)]
-const NUM_BLOCKS_PER_FILE: usize = 4;
+pub(crate) const NUM_BLOCKS_PER_FILE: usize = 4;
 
-const FILE_NUM_BYTES: usize = FRAME_NUM_BYTES * NUM_BLOCKS_PER_FILE;
+pub(crate) const FILE_NUM_BYTES: usize = FRAME_NUM_BYTES * NUM_BLOCKS_PER_FILE;
 #[cfg(test)]
 mod tests;
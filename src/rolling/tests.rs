@@ -186,3 +186,200 @@ fn test_directory_truncate() {
         assert_eq!(&writer.list_file_numbers(), &[3]);
     }
 }
+
+#[test]
+fn test_index_written_on_roll() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut writer: RollingWriter = RollingReader::open(tmp_dir.path())
+        .unwrap()
+        .into_writer()
+        .unwrap();
+    let buf = vec![1u8; FRAME_NUM_BYTES];
+    writer.index_records("q1", 0..=9);
+    writer.write(&buf).unwrap();
+    writer.commit_indexed_records(true);
+    writer.index_records("q2", 3..=3);
+    writer.write(&buf).unwrap();
+    writer.commit_indexed_records(true);
+    // Records that failed to be written are not indexed.
+    writer.index_records("q3", 0..=0);
+    writer.commit_indexed_records(false);
+    // Records are indexed on the file they start in, even if their write rolls the file.
+    writer.index_records("q1", 10..=10);
+    for _ in 0..NUM_BLOCKS_PER_FILE - 1 {
+        writer.write(&buf).unwrap();
+    }
+    writer.commit_indexed_records(true);
+    assert_eq!(&writer.list_file_numbers(), &[0, 1]);
+    assert_eq!(FileIndex::load(tmp_dir.path(), 1).unwrap(), None);
+    let index = FileIndex::load(tmp_dir.path(), 0).unwrap().unwrap();
+    assert_eq!(index.first_indexed_block(), 0);
+    assert_eq!(
+        index.get("q1"),
+        Some(&QueueFileRange {
+            first_position: 0,
+            last_position: 10,
+            first_block_id: 0,
+            last_block_id: 2,
+        })
+    );
+    assert_eq!(
+        index.get("q2"),
+        Some(&QueueFileRange {
+            first_position: 3,
+            last_position: 3,
+            first_block_id: 1,
+            last_block_id: 1,
+        })
+    );
+    assert_eq!(index.get("q3"), None);
+}
+
+#[test]
+fn test_index_removed_on_gc() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let reader = RollingReader::open(tmp_dir.path()).unwrap();
+    let file_0 = reader.current_file().clone();
+    let mut writer: RollingWriter = reader.into_writer().unwrap();
+    let buf = vec![1u8; FRAME_NUM_BYTES];
+    writer.index_records("q1", 0..=0);
+    writer.write(&buf).unwrap();
+    writer.commit_indexed_records(true);
+    for _ in 0..NUM_BLOCKS_PER_FILE {
+        writer.write(&buf).unwrap();
+    }
+    assert!(FileIndex::load(tmp_dir.path(), 0).unwrap().is_some());
+    drop(file_0);
+    writer.directory.gc().unwrap();
+    assert_eq!(&writer.list_file_numbers(), &[1]);
+    assert_eq!(FileIndex::load(tmp_dir.path(), 0).unwrap(), None);
+}
+
+fn write_files_and_gc(dir: &std::path::Path, gc_action: crate::GcAction, num_files: usize) {
+    let reader = RollingReader::open(dir).unwrap();
+    let file_0 = reader.current_file().clone();
+    let mut writer: RollingWriter = reader.into_writer().unwrap();
+    writer.directory.set_gc_action(gc_action);
+    let buf = vec![1u8; FRAME_NUM_BYTES];
+    for _ in 0..NUM_BLOCKS_PER_FILE * (num_files - 1) + 1 {
+        writer.write(&buf).unwrap();
+    }
+    drop(file_0);
+    writer.directory.gc().unwrap();
+    assert_eq!(&writer.list_file_numbers(), &[(num_files - 1) as u64]);
+}
+
+#[test]
+fn test_gc_failure_not_counted() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let archive_parent_dir = tempfile::tempdir().unwrap();
+    // The archive directory cannot be created under a regular file.
+    let archive_file = archive_parent_dir.path().join("file");
+    std::fs::write(&archive_file, b"").unwrap();
+    let reader = RollingReader::open(tmp_dir.path()).unwrap();
+    let file_0 = reader.current_file().clone();
+    let mut writer: RollingWriter = reader.into_writer().unwrap();
+    writer.directory.set_gc_action(crate::GcAction::Archive {
+        directory: archive_file.join("archive"),
+        max_num_bytes: u64::MAX,
+        max_age: std::time::Duration::from_secs(3600),
+    });
+    let buf = vec![1u8; FRAME_NUM_BYTES];
+    for _ in 0..NUM_BLOCKS_PER_FILE + 1 {
+        writer.write(&buf).unwrap();
+    }
+    drop(file_0);
+    assert!(writer.directory.gc().is_err());
+    assert_eq!(writer.directory.stats.snapshot().num_gced_files, 0);
+}
+
+#[test]
+fn test_gc_archive() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let archive_dir = tempfile::tempdir().unwrap();
+    let gc_action = crate::GcAction::Archive {
+        directory: archive_dir.path().to_path_buf(),
+        max_num_bytes: u64::MAX,
+        max_age: std::time::Duration::from_secs(3600),
+    };
+    write_files_and_gc(tmp_dir.path(), gc_action, 3);
+    assert!(!directory::filepath(tmp_dir.path(), &FileNumber::for_test(0)).exists());
+    for file_number in [0, 1] {
+        let archived_filepath =
+            directory::filepath(archive_dir.path(), &FileNumber::for_test(file_number));
+        assert!(archived_filepath.exists());
+        assert!(FileIndex::load(archive_dir.path(), file_number)
+            .unwrap()
+            .is_some());
+    }
+}
+
+#[test]
+fn test_archive_errors() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let archive_dir = tempfile::tempdir().unwrap();
+    let filepath = tmp_dir.path().join("wal-00000000000000000000");
+    std::fs::write(&filepath, b"wal").unwrap();
+    // Renaming a file over a directory fails: the error is returned, and nothing is copied.
+    let archived_filepath = archive_dir.path().join("wal-00000000000000000000");
+    std::fs::create_dir(&archived_filepath).unwrap();
+    assert!(archive::archive_file(&filepath, archive_dir.path()).is_err());
+    assert!(filepath.exists());
+    std::fs::remove_dir(&archived_filepath).unwrap();
+    // The fallback used across filesystems.
+    archive::copy_to_archive(&filepath, &archived_filepath).unwrap();
+    assert!(!filepath.exists());
+    assert_eq!(std::fs::read(&archived_filepath).unwrap(), b"wal");
+    assert_eq!(std::fs::read_dir(archive_dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_gc_archive_size_limit() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let archive_dir = tempfile::tempdir().unwrap();
+    let gc_action = crate::GcAction::Archive {
+        directory: archive_dir.path().to_path_buf(),
+        max_num_bytes: FILE_NUM_BYTES as u64,
+        max_age: std::time::Duration::from_secs(3600),
+    };
+    write_files_and_gc(tmp_dir.path(), gc_action, 3);
+    assert!(!directory::filepath(archive_dir.path(), &FileNumber::for_test(0)).exists());
+    assert_eq!(FileIndex::load(archive_dir.path(), 0).unwrap(), None);
+    assert!(directory::filepath(archive_dir.path(), &FileNumber::for_test(1)).exists());
+}
+
+#[test]
+fn test_gc_archive_age_limit() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let archive_dir = tempfile::tempdir().unwrap();
+    let gc_action = crate::GcAction::Archive {
+        directory: archive_dir.path().to_path_buf(),
+        max_num_bytes: u64::MAX,
+        max_age: std::time::Duration::ZERO,
+    };
+    write_files_and_gc(tmp_dir.path(), gc_action, 3);
+    assert_eq!(std::fs::read_dir(archive_dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn test_gc_callback() {
+    use std::sync::{Arc, Mutex};
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let gced_files: Arc<Mutex<Vec<std::path::PathBuf>>> = Default::default();
+    let gced_files_clone = gced_files.clone();
+    let gc_action = crate::GcAction::Callback(Arc::new(move |path: &std::path::Path| {
+        assert!(path.exists());
+        gced_files_clone.lock().unwrap().push(path.to_path_buf());
+    }));
+    write_files_and_gc(tmp_dir.path(), gc_action, 3);
+    let gced_files = gced_files.lock().unwrap();
+    assert_eq!(
+        &gced_files[..],
+        &[
+            directory::filepath(tmp_dir.path(), &FileNumber::for_test(0)),
+            directory::filepath(tmp_dir.path(), &FileNumber::for_test(1)),
+        ]
+    );
+    assert!(!gced_files[0].exists());
+}
@@ -0,0 +1,172 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::PersistAction;
+
+/// Upper bounds of the buckets of latency histograms.
+pub const LATENCY_BUCKETS: [Duration; 12] = [
+    Duration::from_micros(10),
+    Duration::from_micros(50),
+    Duration::from_micros(100),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(5),
+];
+
+/// Distribution of the latency of an operation.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    /// Number of samples per bucket.
+    ///
+    /// Bucket `i` counts the samples lower than or equal to `LATENCY_BUCKETS[i]`, and greater
+    /// than the previous bound. The last bucket counts the samples greater than every bound.
+    pub bucket_counts: Vec<u64>,
+    /// Total number of samples.
+    pub count: u64,
+    /// Sum of all samples.
+    pub sum: Duration,
+}
+
+/// Cumulative IO statistics of a [`MultiRecordLog`](crate::MultiRecordLog), since it was
+/// opened.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IoStats {
+    /// Bytes written to wal files, including padding.
+    pub bytes_written: u64,
+    /// Bytes written to fill the end of blocks.
+    pub padding_bytes: u64,
+    /// Number of frames written.
+    pub num_frames: u64,
+    /// Number of persists that flushed the buffer without fsyncing it.
+    pub num_flushes: u64,
+    /// Number of persists that flushed and fsynced the buffer.
+    pub num_fsyncs: u64,
+    /// Number of times the writer rolled to the next wal file.
+    pub num_rolls: u64,
+    /// Number of wal files GCed.
+    pub num_gced_files: u64,
+    /// Latency of persists, flushes and fsyncs alike.
+    pub persist_latency: LatencyHistogram,
+    /// Latency of rolls to the next wal file.
+    pub roll_latency: LatencyHistogram,
+}
+
+#[derive(Default)]
+struct AtomicHistogram {
+    bucket_counts: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+}
+
+impl AtomicHistogram {
+    fn record(&self, latency: Duration) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|upper_bound| latency <= *upper_bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.bucket_counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> LatencyHistogram {
+        let bucket_counts: Vec<u64> = self
+            .bucket_counts
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect();
+        LatencyHistogram {
+            count: bucket_counts.iter().sum(),
+            bucket_counts,
+            sum: Duration::from_micros(self.sum_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Collects the IO statistics. It is shared by the different layers of the writer.
+#[derive(Default)]
+pub(crate) struct IoStatsRecorder {
+    bytes_written: AtomicU64,
+    padding_bytes: AtomicU64,
+    num_frames: AtomicU64,
+    num_flushes: AtomicU64,
+    num_fsyncs: AtomicU64,
+    num_rolls: AtomicU64,
+    num_gced_files: AtomicU64,
+    persist_latency: AtomicHistogram,
+    roll_latency: AtomicHistogram,
+}
+
+impl IoStatsRecorder {
+    pub fn record_write(&self, num_bytes: usize) {
+        self.bytes_written
+            .fetch_add(num_bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_frame(&self) {
+        self.num_frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_padding(&self, num_bytes: usize) {
+        self.padding_bytes
+            .fetch_add(num_bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_persist(&self, persist_action: PersistAction, latency: Duration) {
+        if persist_action.is_fsync() {
+            self.num_fsyncs.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.num_flushes.fetch_add(1, Ordering::Relaxed);
+        }
+        self.persist_latency.record(latency);
+    }
+
+    pub fn record_roll(&self, latency: Duration) {
+        self.num_rolls.fetch_add(1, Ordering::Relaxed);
+        self.roll_latency.record(latency);
+    }
+
+    pub fn record_gced_file(&self) {
+        self.num_gced_files.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> IoStats {
+        IoStats {
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            padding_bytes: self.padding_bytes.load(Ordering::Relaxed),
+            num_frames: self.num_frames.load(Ordering::Relaxed),
+            num_flushes: self.num_flushes.load(Ordering::Relaxed),
+            num_fsyncs: self.num_fsyncs.load(Ordering::Relaxed),
+            num_rolls: self.num_rolls.load(Ordering::Relaxed),
+            num_gced_files: self.num_gced_files.load(Ordering::Relaxed),
+            persist_latency: self.persist_latency.snapshot(),
+            roll_latency: self.roll_latency.snapshot(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_histogram() {
+        let histogram = AtomicHistogram::default();
+        histogram.record(Duration::from_micros(5));
+        histogram.record(Duration::from_micros(10));
+        histogram.record(Duration::from_micros(11));
+        histogram.record(Duration::from_secs(10));
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.count, 4);
+        assert_eq!(snapshot.bucket_counts.len(), LATENCY_BUCKETS.len() + 1);
+        assert_eq!(snapshot.bucket_counts[0], 2);
+        assert_eq!(snapshot.bucket_counts[1], 1);
+        assert_eq!(snapshot.bucket_counts[LATENCY_BUCKETS.len()], 1);
+        assert_eq!(snapshot.sum, Duration::from_micros(10_000_026));
+    }
+}
@@ -2,19 +2,35 @@ use std::borrow::Cow;
 
 use bytes::Buf;
 
-use crate::{MultiRecordLog, Record};
+use crate::{MultiRecordLog, OwnedRecord, Record};
 
 fn read_all_records<'a>(multi_record_log: &'a MultiRecordLog, queue: &str) -> Vec<Cow<'a, [u8]>> {
     let mut records = Vec::new();
-    let mut next_pos = u64::default();
-    for Record { position, payload } in multi_record_log.range(queue, next_pos..).unwrap() {
+    for (next_pos, Record { position, payload }) in
+        (0u64..).zip(multi_record_log.range(queue, 0..).unwrap())
+    {
         assert_eq!(position, next_pos);
         records.push(payload);
-        next_pos += 1;
     }
     records
 }
 
+/// Appends ten records of 20KB to the queue. A file is 4 blocks of 32KB in tests: this is enough
+/// to roll to the next file.
+///
+/// Returns the number of bytes written to the WAL.
+fn append_until_rolled(multi_record_log: &mut MultiRecordLog, queue: &str) -> u64 {
+    let payload = vec![0u8; 20_000];
+    let mut wal_bytes_written = 0;
+    for _ in 0..10 {
+        wal_bytes_written += multi_record_log
+            .append_record(queue, None, &payload[..])
+            .unwrap()
+            .wal_bytes_written;
+    }
+    wal_bytes_written
+}
+
 #[test]
 fn test_multi_record_log_new() {
     let tempdir = tempfile::tempdir().unwrap();
@@ -462,3 +478,1171 @@ fn test_last_record() {
     let last_record = multi_record_log.last_record("queue1").unwrap();
     assert!(last_record.is_none());
 }
+
+#[test]
+fn test_file_index() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+    multi_record_log.create_queue("queue1").unwrap();
+    multi_record_log.create_queue("queue2").unwrap();
+    let payload = vec![0u8; 20_000];
+    multi_record_log
+        .append_records("queue2", None, std::iter::repeat(&payload[..]).take(2))
+        .unwrap();
+    append_until_rolled(&mut multi_record_log, "queue1");
+    assert_eq!(&multi_record_log.list_file_numbers(), &[0, 1]);
+    let index = crate::FileIndex::load(tempdir.path(), 0).unwrap().unwrap();
+    let queue1_range = index.get("queue1").unwrap();
+    assert_eq!(queue1_range.first_position, 0);
+    assert_eq!(queue1_range.first_block_id, 1);
+    assert!(queue1_range.last_position < 9);
+    let queue2_range = index.get("queue2").unwrap();
+    assert_eq!(queue2_range.first_position, 0);
+    assert_eq!(queue2_range.last_position, 1);
+    assert_eq!(queue2_range.first_block_id, 0);
+}
+
+#[test]
+fn test_open_with_gc_action() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let tempdir = tempfile::tempdir().unwrap();
+    let num_gced_files = Arc::new(AtomicUsize::new(0));
+    let num_gced_files_clone = num_gced_files.clone();
+    let options = crate::MultiRecordLogOptions {
+        gc_action: crate::GcAction::Callback(Arc::new(move |_| {
+            num_gced_files_clone.fetch_add(1, Ordering::Relaxed);
+        })),
+        ..Default::default()
+    };
+    let mut multi_record_log = MultiRecordLog::open_with_options(tempdir.path(), options).unwrap();
+    multi_record_log.create_queue("queue").unwrap();
+    append_until_rolled(&mut multi_record_log, "queue");
+    assert_eq!(&multi_record_log.list_file_numbers(), &[0, 1]);
+    multi_record_log.truncate("queue", ..=9).unwrap();
+    assert_eq!(&multi_record_log.list_file_numbers(), &[1]);
+    assert_eq!(num_gced_files.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn test_disk_full_read_only_mode() {
+    let tempdir = tempfile::tempdir().unwrap();
+    {
+        let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        multi_record_log.create_queue("queue").unwrap();
+        multi_record_log
+            .append_records("queue", None, [&b"1"[..], &b"2"[..]].into_iter())
+            .unwrap();
+        multi_record_log.simulate_disk_full(true);
+        assert!(matches!(
+            multi_record_log.append_record("queue", None, &b"3"[..]),
+            Err(crate::error::AppendError::DiskFull)
+        ));
+        assert!(multi_record_log.is_degraded());
+        assert!(matches!(
+            multi_record_log.create_queue("queue2"),
+            Err(crate::error::CreateQueueError::DiskFull)
+        ));
+        assert!(matches!(
+            multi_record_log.delete_queue("queue"),
+            Err(crate::error::DeleteQueueError::DiskFull)
+        ));
+        // reads and truncates keep working.
+        assert_eq!(multi_record_log.last_position("queue").unwrap(), Some(1));
+        let truncate_outcome = multi_record_log.truncate("queue", ..=0).unwrap();
+        assert_eq!(truncate_outcome.evicted_records, 1);
+        assert_eq!(
+            multi_record_log
+                .range("queue", ..)
+                .unwrap()
+                .collect::<Vec<_>>(),
+            &[Record::new(1, b"2")]
+        );
+        assert!(multi_record_log.is_degraded());
+
+        multi_record_log.simulate_disk_full(false);
+        assert_eq!(
+            multi_record_log
+                .append_record("queue", None, &b"3"[..])
+                .unwrap()
+                .last_position,
+            Some(2)
+        );
+        assert!(!multi_record_log.is_degraded());
+    }
+    {
+        let multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        assert_eq!(
+            multi_record_log
+                .range("queue", ..)
+                .unwrap()
+                .collect::<Vec<_>>(),
+            &[Record::new(1, b"2"), Record::new(2, b"3")]
+        );
+    }
+}
+
+#[test]
+fn test_disk_full_while_persisting() {
+    let tempdir = tempfile::tempdir().unwrap();
+    {
+        let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        multi_record_log.create_queue("queue").unwrap();
+        multi_record_log
+            .append_record("queue", None, &b"1"[..])
+            .unwrap();
+        multi_record_log.simulate_disk_full(true);
+        multi_record_log
+            .persist(crate::PersistAction::Flush)
+            .unwrap_err();
+        assert!(multi_record_log.is_degraded());
+        multi_record_log.simulate_disk_full(false);
+        multi_record_log
+            .append_record("queue", None, &b"2"[..])
+            .unwrap();
+    }
+    {
+        let multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        assert_eq!(
+            &read_all_records(&multi_record_log, "queue")[..],
+            &[b"1".as_slice(), b"2".as_slice()]
+        );
+    }
+}
+
+#[test]
+fn test_disk_full_torn_write_recovery() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let large_payload = vec![7u8; 3 * crate::BLOCK_NUM_BYTES];
+    {
+        let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        multi_record_log.create_queue("queue").unwrap();
+        multi_record_log
+            .append_record("queue", None, &b"1"[..])
+            .unwrap();
+        // The disk gets full in the middle of the second frame of the record.
+        multi_record_log.simulate_disk_full_after(crate::BLOCK_NUM_BYTES + 100);
+        assert!(matches!(
+            multi_record_log.append_record("queue", None, &large_payload[..]),
+            Err(crate::error::AppendError::DiskFull)
+        ));
+        assert!(multi_record_log.is_degraded());
+        multi_record_log.simulate_disk_full(false);
+        multi_record_log
+            .append_record("queue", None, &b"2"[..])
+            .unwrap();
+    }
+    {
+        // The torn record is skipped, and the records written after it are read.
+        let multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        assert_eq!(
+            multi_record_log
+                .range("queue", ..)
+                .unwrap()
+                .collect::<Vec<_>>(),
+            &[Record::new(0, b"1"), Record::new(1, b"2")]
+        );
+    }
+}
+
+#[test]
+fn test_disk_full_background_persist() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let options = crate::MultiRecordLogOptions {
+        persist_policy: crate::PersistPolicy::OnDelay {
+            interval: std::time::Duration::from_millis(20),
+            action: crate::PersistAction::Flush,
+        },
+        background_persist: true,
+        ..Default::default()
+    };
+    let mut multi_record_log = MultiRecordLog::open_with_options(tempdir.path(), options).unwrap();
+    multi_record_log.create_queue("queue").unwrap();
+    multi_record_log
+        .append_record("queue", None, &b"1"[..])
+        .unwrap();
+    multi_record_log.simulate_disk_full(true);
+    let start = std::time::Instant::now();
+    while !multi_record_log.is_degraded() {
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert!(matches!(
+        multi_record_log.append_record("queue", None, &b"2"[..]),
+        Err(crate::error::AppendError::DiskFull)
+    ));
+    multi_record_log.simulate_disk_full(false);
+    multi_record_log
+        .append_record("queue", None, &b"2"[..])
+        .unwrap();
+    assert!(!multi_record_log.is_degraded());
+}
+
+#[test]
+fn test_background_persist_appends_do_not_persist() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let options = crate::MultiRecordLogOptions {
+        persist_policy: crate::PersistPolicy::OnDelay {
+            interval: std::time::Duration::from_millis(20),
+            action: crate::PersistAction::Flush,
+        },
+        background_persist: true,
+        ..Default::default()
+    };
+    let mut multi_record_log = MultiRecordLog::open_with_options(tempdir.path(), options).unwrap();
+    multi_record_log.create_queue("queue").unwrap();
+    for _ in 0..3 {
+        std::thread::sleep(std::time::Duration::from_millis(30));
+        let outcome = multi_record_log
+            .append_record("queue", None, &b"1"[..])
+            .unwrap();
+        // The interval is elapsed, but persisting is left to the background thread.
+        assert!(!outcome.persisted);
+    }
+}
+
+fn open_batched(
+    dir: &std::path::Path,
+    max_bytes: u64,
+    max_records: u64,
+    max_delay: std::time::Duration,
+) -> MultiRecordLog {
+    let options = crate::MultiRecordLogOptions {
+        persist_policy: crate::PersistPolicy::Batched {
+            max_bytes,
+            max_records,
+            max_delay,
+            action: crate::PersistAction::Flush,
+        },
+        ..Default::default()
+    };
+    MultiRecordLog::open_with_options(dir, options).unwrap()
+}
+
+#[test]
+fn test_batched_persist_max_records() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let mut multi_record_log = open_batched(tempdir.path(), u64::MAX, 3, std::time::Duration::MAX);
+    multi_record_log.create_queue("queue").unwrap();
+    let persisted: Vec<bool> = (0..7)
+        .map(|_| {
+            multi_record_log
+                .append_record("queue", None, &b"hello"[..])
+                .unwrap()
+                .persisted
+        })
+        .collect();
+    assert_eq!(persisted, [false, false, true, false, false, true, false]);
+    // A batch of records counts every record.
+    let outcome = multi_record_log
+        .append_records("queue", None, std::iter::repeat(&b"hello"[..]).take(2))
+        .unwrap();
+    assert!(outcome.persisted);
+}
+
+#[test]
+fn test_batched_persist_reset_by_explicit_persist() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let mut multi_record_log = open_batched(tempdir.path(), u64::MAX, 3, std::time::Duration::MAX);
+    multi_record_log.create_queue("queue").unwrap();
+    let append = |multi_record_log: &mut MultiRecordLog| {
+        multi_record_log
+            .append_record("queue", None, &b"hello"[..])
+            .unwrap()
+            .persisted
+    };
+    assert!(!append(&mut multi_record_log));
+    assert!(!append(&mut multi_record_log));
+    multi_record_log
+        .persist(crate::PersistAction::Flush)
+        .unwrap();
+    assert!(!append(&mut multi_record_log));
+    assert!(!append(&mut multi_record_log));
+    // Creating a queue fsyncs the log.
+    multi_record_log.create_queue("other-queue").unwrap();
+    assert!(!append(&mut multi_record_log));
+    assert!(!append(&mut multi_record_log));
+    assert!(append(&mut multi_record_log));
+}
+
+#[test]
+fn test_batched_persist_max_bytes() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let mut multi_record_log =
+        open_batched(tempdir.path(), 1_000, u64::MAX, std::time::Duration::MAX);
+    multi_record_log.create_queue("queue").unwrap();
+    let payload = vec![0u8; 400];
+    let outcome = multi_record_log
+        .append_record("queue", None, &payload[..])
+        .unwrap();
+    assert!(!outcome.persisted);
+    let outcome = multi_record_log
+        .append_record("queue", None, &payload[..])
+        .unwrap();
+    assert!(!outcome.persisted);
+    let outcome = multi_record_log
+        .append_record("queue", None, &payload[..])
+        .unwrap();
+    assert!(outcome.persisted);
+    let outcome = multi_record_log
+        .append_record("queue", None, &payload[..])
+        .unwrap();
+    assert!(!outcome.persisted);
+}
+
+#[test]
+fn test_batched_persist_max_delay() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let mut multi_record_log = open_batched(
+        tempdir.path(),
+        u64::MAX,
+        u64::MAX,
+        std::time::Duration::from_millis(50),
+    );
+    multi_record_log.create_queue("queue").unwrap();
+    let outcome = multi_record_log
+        .append_record("queue", None, &b"hello"[..])
+        .unwrap();
+    assert!(!outcome.persisted);
+    std::thread::sleep(std::time::Duration::from_millis(60));
+    let outcome = multi_record_log
+        .append_record("queue", None, &b"hello"[..])
+        .unwrap();
+    assert!(outcome.persisted);
+    let outcome = multi_record_log
+        .append_record("queue", None, &b"hello"[..])
+        .unwrap();
+    assert!(!outcome.persisted);
+}
+
+fn wal_contains(dir: &std::path::Path, needle: &[u8]) -> bool {
+    std::fs::read_dir(dir).unwrap().any(|dir_entry| {
+        let content = std::fs::read(dir_entry.unwrap().path()).unwrap();
+        content.windows(needle.len()).any(|window| window == needle)
+    })
+}
+
+#[test]
+fn test_background_persist() {
+    let open_options = |background_persist: bool| crate::MultiRecordLogOptions {
+        persist_policy: crate::PersistPolicy::OnDelay {
+            interval: std::time::Duration::from_millis(20),
+            action: crate::PersistAction::Flush,
+        },
+        background_persist,
+        ..Default::default()
+    };
+    for background_persist in [false, true] {
+        let tempdir = tempfile::tempdir().unwrap();
+        let mut multi_record_log =
+            MultiRecordLog::open_with_options(tempdir.path(), open_options(background_persist))
+                .unwrap();
+        multi_record_log.create_queue("queue").unwrap();
+        multi_record_log
+            .append_record("queue", None, &b"background"[..])
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert_eq!(
+            wal_contains(tempdir.path(), b"background"),
+            background_persist
+        );
+        drop(multi_record_log);
+        assert!(wal_contains(tempdir.path(), b"background"));
+    }
+}
+
+#[test]
+fn test_durable_position() {
+    let tempdir = tempfile::tempdir().unwrap();
+    {
+        let mut multi_record_log =
+            MultiRecordLog::open_with_prefs(tempdir.path(), crate::PersistPolicy::DoNothing)
+                .unwrap();
+        multi_record_log.create_queue("queue").unwrap();
+        assert_eq!(multi_record_log.durable_position("queue").unwrap(), None);
+        assert!(multi_record_log.durable_position("missing").is_err());
+        multi_record_log
+            .append_records("queue", None, [&b"1"[..], &b"2"[..]].into_iter())
+            .unwrap();
+        assert_eq!(multi_record_log.durable_position("queue").unwrap(), None);
+        multi_record_log
+            .persist(crate::PersistAction::Flush)
+            .unwrap();
+        assert_eq!(multi_record_log.durable_position("queue").unwrap(), None);
+        multi_record_log
+            .persist(crate::PersistAction::FlushAndFsync)
+            .unwrap();
+        assert_eq!(multi_record_log.durable_position("queue").unwrap(), Some(1));
+        multi_record_log
+            .append_record("queue", None, &b"3"[..])
+            .unwrap();
+        assert_eq!(multi_record_log.durable_position("queue").unwrap(), Some(1));
+    }
+    {
+        // Replayed records are durable, once the log was fsynced when opening it.
+        let multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        assert_eq!(multi_record_log.stats().num_fsyncs, 1);
+        assert_eq!(multi_record_log.durable_position("queue").unwrap(), Some(2));
+    }
+}
+
+#[test]
+fn test_durability_watcher_wait_for() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let options = crate::MultiRecordLogOptions {
+        persist_policy: crate::PersistPolicy::OnDelay {
+            interval: std::time::Duration::from_millis(20),
+            action: crate::PersistAction::FlushAndFsync,
+        },
+        background_persist: true,
+        ..Default::default()
+    };
+    let mut multi_record_log = MultiRecordLog::open_with_options(tempdir.path(), options).unwrap();
+    multi_record_log.create_queue("queue").unwrap();
+    let durability_watcher = multi_record_log.durability_watcher();
+    assert!(!durability_watcher.wait_for("queue", 0, std::time::Duration::from_millis(1)));
+    let waiter = std::thread::spawn(move || {
+        durability_watcher.wait_for("queue", 0, std::time::Duration::from_secs(10))
+    });
+    multi_record_log
+        .append_record("queue", None, &b"1"[..])
+        .unwrap();
+    // No further operation: the background persister makes the record durable.
+    assert!(waiter.join().unwrap());
+    assert_eq!(multi_record_log.durable_position("queue").unwrap(), Some(0));
+}
+
+#[test]
+fn test_durability_class_volatile() {
+    let tempdir = tempfile::tempdir().unwrap();
+    {
+        let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        let outcome = multi_record_log
+            .create_queue_with_class("volatile", crate::DurabilityClass::Volatile)
+            .unwrap();
+        assert_eq!(outcome.wal_bytes_written, 0);
+        let outcome = multi_record_log
+            .append_records("volatile", None, [&b"1"[..], &b"2"[..]].into_iter())
+            .unwrap();
+        assert_eq!(outcome.last_position, Some(1));
+        assert_eq!(outcome.wal_bytes_written, 0);
+        assert!(!outcome.persisted);
+        assert_eq!(
+            multi_record_log
+                .range("volatile", ..)
+                .unwrap()
+                .collect::<Vec<_>>(),
+            [Record::new(0, b"1"), Record::new(1, b"2")]
+        );
+        // Volatile queues keep working while the disk is full.
+        multi_record_log.simulate_disk_full(true);
+        multi_record_log
+            .append_record("volatile", None, &b"3"[..])
+            .unwrap();
+        multi_record_log.truncate("volatile", ..=1).unwrap();
+        assert_eq!(multi_record_log.range("volatile", ..).unwrap().count(), 1);
+        multi_record_log.simulate_disk_full(false);
+        assert_eq!(multi_record_log.durable_position("volatile").unwrap(), None);
+        multi_record_log
+            .persist(crate::PersistAction::FlushAndFsync)
+            .unwrap();
+        assert_eq!(multi_record_log.durable_position("volatile").unwrap(), None);
+    }
+    assert!(!wal_contains(tempdir.path(), b"volatile"));
+    let multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+    assert!(!multi_record_log.queue_exists("volatile"));
+}
+
+#[test]
+fn test_durability_class_persist() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let mut multi_record_log =
+        MultiRecordLog::open_with_prefs(tempdir.path(), crate::PersistPolicy::DoNothing).unwrap();
+    multi_record_log.create_queue("delayed").unwrap();
+    multi_record_log
+        .create_queue_with_class("critical", crate::DurabilityClass::AlwaysFsync)
+        .unwrap();
+    multi_record_log
+        .create_queue_with_class("flushed", crate::DurabilityClass::Flush)
+        .unwrap();
+    assert!(
+        !multi_record_log
+            .append_record("delayed", None, &b"delayed-payload"[..])
+            .unwrap()
+            .persisted
+    );
+    assert!(!wal_contains(tempdir.path(), b"delayed-payload"));
+    assert!(
+        multi_record_log
+            .append_record("flushed", None, &b"flushed-payload"[..])
+            .unwrap()
+            .persisted
+    );
+    assert!(wal_contains(tempdir.path(), b"delayed-payload"));
+    assert_eq!(multi_record_log.durable_position("delayed").unwrap(), None);
+    assert!(
+        multi_record_log
+            .append_record("critical", None, &b"critical-payload"[..])
+            .unwrap()
+            .persisted
+    );
+    // The fsync of the critical queue covers the other queues.
+    assert_eq!(
+        multi_record_log.durable_position("delayed").unwrap(),
+        Some(0)
+    );
+    assert_eq!(
+        multi_record_log.durable_position("flushed").unwrap(),
+        Some(0)
+    );
+    assert_eq!(
+        multi_record_log.durable_position("critical").unwrap(),
+        Some(0)
+    );
+}
+
+#[test]
+fn test_durability_class_survives_reopen_and_gc() {
+    let tempdir = tempfile::tempdir().unwrap();
+    {
+        let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        multi_record_log
+            .create_queue_with_class("critical", crate::DurabilityClass::AlwaysFsync)
+            .unwrap();
+        multi_record_log.create_queue("other").unwrap();
+        append_until_rolled(&mut multi_record_log, "other");
+        multi_record_log
+            .append_record("critical", None, &b"1"[..])
+            .unwrap();
+        // The file holding the durability class record gets GCed.
+        multi_record_log.truncate("other", ..=9).unwrap();
+        assert_eq!(&multi_record_log.list_file_numbers(), &[1]);
+    }
+    let multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+    assert_eq!(
+        multi_record_log.durability_class("critical").unwrap(),
+        crate::DurabilityClass::AlwaysFsync
+    );
+    assert_eq!(
+        multi_record_log.durability_class("other").unwrap(),
+        crate::DurabilityClass::Delayed
+    );
+}
+
+#[test]
+fn test_io_stats() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+    let initial_stats = multi_record_log.stats();
+    multi_record_log.create_queue("queue").unwrap();
+    let wal_bytes_written = append_until_rolled(&mut multi_record_log, "queue");
+    multi_record_log.truncate("queue", ..=9).unwrap();
+    let stats = multi_record_log.stats();
+    assert!(stats.bytes_written >= initial_stats.bytes_written + wal_bytes_written);
+    assert!(stats.num_frames >= 10);
+    assert!(stats.num_fsyncs > initial_stats.num_fsyncs);
+    assert!(stats.num_flushes >= initial_stats.num_flushes + 10);
+    assert_eq!(stats.num_rolls, 1);
+    assert_eq!(stats.num_gced_files, 1);
+    assert_eq!(stats.roll_latency.count, 1);
+    assert_eq!(
+        stats.persist_latency.count,
+        stats.num_flushes + stats.num_fsyncs
+    );
+    assert_eq!(
+        stats.persist_latency.bucket_counts.iter().sum::<u64>(),
+        stats.persist_latency.count
+    );
+}
+
+#[test]
+fn test_io_stats_padding() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+    multi_record_log.create_queue("queue").unwrap();
+    let padding_bytes_before = multi_record_log.stats().padding_bytes;
+    // Disk full recovery pads the current block.
+    multi_record_log.simulate_disk_full(true);
+    assert!(multi_record_log
+        .append_record("queue", None, &b"hello"[..])
+        .is_err());
+    multi_record_log.simulate_disk_full(false);
+    multi_record_log
+        .append_record("queue", None, &b"hello"[..])
+        .unwrap();
+    assert!(multi_record_log.stats().padding_bytes > padding_bytes_before);
+}
+
+#[derive(Default)]
+struct RecordingListener {
+    events: std::sync::Mutex<Vec<String>>,
+}
+
+impl RecordingListener {
+    fn push(&self, event: String) {
+        self.events.lock().unwrap().push(event);
+    }
+
+    fn take_events(&self) -> Vec<String> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+}
+
+impl crate::LogListener for RecordingListener {
+    fn on_queue_created(&self, queue: &str) {
+        self.push(format!("created {queue}"));
+    }
+
+    fn on_queue_deleted(&self, queue: &str) {
+        self.push(format!("deleted {queue}"));
+    }
+
+    fn on_records_appended(
+        &self,
+        queue: &str,
+        positions: std::ops::RangeInclusive<u64>,
+        wal_bytes_written: u64,
+    ) {
+        assert!(wal_bytes_written > 0);
+        self.push(format!("appended {queue} {positions:?}"));
+    }
+
+    fn on_truncated(
+        &self,
+        queue: &str,
+        position: u64,
+        evicted_records: usize,
+        _wal_bytes_written: u64,
+    ) {
+        self.push(format!("truncated {queue} {position} {evicted_records}"));
+    }
+
+    fn on_file_rolled(&self, previous_file_number: u64, file_number: u64) {
+        self.push(format!("rolled {previous_file_number} {file_number}"));
+    }
+
+    fn on_file_gced(&self, file_number: u64, filepath: &std::path::Path) {
+        assert!(!filepath.exists());
+        self.push(format!("gced {file_number}"));
+    }
+
+    fn on_corruption(&self, file_number: u64) {
+        self.push(format!("corruption {file_number}"));
+    }
+
+    fn on_gc_blocked(&self, gc_blockage: &crate::GcBlockage) {
+        self.push(format!(
+            "gc blocked {} {}",
+            gc_blockage.queue, gc_blockage.file_number
+        ));
+    }
+}
+
+#[test]
+fn test_log_listener() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let listener = std::sync::Arc::new(RecordingListener::default());
+    let options = crate::MultiRecordLogOptions {
+        listener: Some(listener.clone()),
+        ..Default::default()
+    };
+    let mut multi_record_log = MultiRecordLog::open_with_options(tempdir.path(), options).unwrap();
+    multi_record_log.create_queue("queue").unwrap();
+    let payload = vec![0u8; 20_000];
+    multi_record_log
+        .append_records("queue", None, [&payload[..], &payload[..]].into_iter())
+        .unwrap();
+    append_until_rolled(&mut multi_record_log, "queue");
+    multi_record_log.truncate("queue", ..=11).unwrap();
+    multi_record_log.delete_queue("queue").unwrap();
+    let events = listener.take_events();
+    assert_eq!(events[0], "created queue");
+    assert_eq!(events[1], "appended queue 0..=1");
+    assert!(events.contains(&"rolled 0 1".to_string()));
+    let truncate_idx = events
+        .iter()
+        .position(|event| event == "truncated queue 11 12")
+        .unwrap();
+    assert_eq!(events[truncate_idx - 1], "gced 0");
+    assert_eq!(events.last().unwrap(), "deleted queue");
+}
+
+#[test]
+fn test_log_listener_corruption() {
+    use std::io::{Seek, SeekFrom, Write};
+
+    let tempdir = tempfile::tempdir().unwrap();
+    {
+        let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        multi_record_log.create_queue("queue").unwrap();
+        multi_record_log
+            .append_record("queue", None, &b"hello"[..])
+            .unwrap();
+    }
+    let wal_filepath = tempdir.path().join("wal-00000000000000000000");
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .open(wal_filepath)
+        .unwrap();
+    // Corrupt the payload of the first record.
+    file.seek(SeekFrom::Start(30)).unwrap();
+    file.write_all(b"corrupted").unwrap();
+    drop(file);
+    let listener = std::sync::Arc::new(RecordingListener::default());
+    let options = crate::MultiRecordLogOptions {
+        listener: Some(listener.clone()),
+        ..Default::default()
+    };
+    MultiRecordLog::open_with_options(tempdir.path(), options).unwrap();
+    assert_eq!(listener.take_events()[0], "corruption 0");
+}
+
+#[test]
+fn test_resource_usage_per_queue() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+    multi_record_log.create_queue("forgotten").unwrap();
+    multi_record_log.create_queue("active").unwrap();
+    multi_record_log.create_queue("empty").unwrap();
+    append_until_rolled(&mut multi_record_log, "forgotten");
+    multi_record_log
+        .append_record("active", None, &b"hello"[..])
+        .unwrap();
+    assert_eq!(&multi_record_log.list_file_numbers(), &[0, 1]);
+
+    let resource_usage = multi_record_log.resource_usage_per_queue();
+    assert_eq!(resource_usage.len(), 3);
+    let forgotten = resource_usage["forgotten"];
+    assert_eq!(forgotten.num_records, 10);
+    assert!(forgotten.memory_used_bytes >= 200_000);
+    assert_eq!(forgotten.oldest_pinned_file, Some(0));
+    assert_eq!(
+        forgotten.reclaimable_disk_bytes,
+        crate::rolling::FILE_NUM_BYTES
+    );
+    let active = resource_usage["active"];
+    assert_eq!(active.num_records, 1);
+    assert_eq!(active.oldest_pinned_file, Some(1));
+    assert_eq!(active.reclaimable_disk_bytes, 0);
+    let empty = resource_usage["empty"];
+    assert_eq!(empty.num_records, 0);
+    assert_eq!(empty.oldest_pinned_file, None);
+    assert_eq!(empty.reclaimable_disk_bytes, 0);
+
+    multi_record_log.truncate("forgotten", ..=9).unwrap();
+    assert_eq!(&multi_record_log.list_file_numbers(), &[1]);
+    let resource_usage = multi_record_log.resource_usage_per_queue();
+    assert_eq!(resource_usage["forgotten"].num_records, 0);
+    assert_eq!(resource_usage["active"].reclaimable_disk_bytes, 0);
+}
+
+#[test]
+fn test_gc_blockage() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+    multi_record_log.create_queue("forgotten").unwrap();
+    multi_record_log.create_queue("active").unwrap();
+    multi_record_log
+        .append_record("forgotten", None, &b"hello"[..])
+        .unwrap();
+    assert_eq!(multi_record_log.gc_blockage(), None);
+    let start = std::time::Instant::now();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    for last_position in [9, 19] {
+        append_until_rolled(&mut multi_record_log, "active");
+        multi_record_log
+            .truncate("active", ..=last_position)
+            .unwrap();
+    }
+    assert_eq!(&multi_record_log.list_file_numbers(), &[0, 1, 2, 3]);
+    let gc_blockage = multi_record_log.gc_blockage().unwrap();
+    assert_eq!(gc_blockage.queue, "forgotten");
+    assert_eq!(gc_blockage.file_number, 0);
+    assert_eq!(gc_blockage.num_pinned_files, 3);
+    // The file is pinned since the log rolled past it, not since it was created.
+    assert!(gc_blockage.pinned_for < start.elapsed() - std::time::Duration::from_millis(100));
+    multi_record_log.truncate("forgotten", ..=0).unwrap();
+    assert_eq!(multi_record_log.gc_blockage(), None);
+}
+
+#[test]
+fn test_gc_blockage_threshold() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let listener = std::sync::Arc::new(RecordingListener::default());
+    let options = crate::MultiRecordLogOptions {
+        listener: Some(listener.clone()),
+        gc_blockage_threshold: Some(std::time::Duration::ZERO),
+        ..Default::default()
+    };
+    let mut multi_record_log = MultiRecordLog::open_with_options(tempdir.path(), options).unwrap();
+    multi_record_log.create_queue("forgotten").unwrap();
+    multi_record_log.create_queue("active").unwrap();
+    multi_record_log
+        .append_record("forgotten", None, &b"hello"[..])
+        .unwrap();
+    for last_position in [9, 19] {
+        append_until_rolled(&mut multi_record_log, "active");
+        multi_record_log
+            .truncate("active", ..=last_position)
+            .unwrap();
+    }
+    let events = listener.take_events();
+    let gc_blocked_events: Vec<&String> = events
+        .iter()
+        .filter(|event| event.starts_with("gc blocked"))
+        .collect();
+    // The active queue may block GC for a short while, when its records straddle two files.
+    assert_eq!(
+        gc_blocked_events.last().unwrap().as_str(),
+        "gc blocked forgotten 0"
+    );
+    assert_eq!(
+        gc_blocked_events
+            .iter()
+            .filter(|event| event.contains("forgotten"))
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn test_summary() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+    multi_record_log.create_queue("queue").unwrap();
+    multi_record_log
+        .create_queue_with_class("volatile", crate::DurabilityClass::Volatile)
+        .unwrap();
+    let summary = multi_record_log.summary();
+    let queue_summary = &summary.queues["queue"];
+    assert_eq!(queue_summary.num_records, 0);
+    assert_eq!(queue_summary.num_files, 0);
+    assert!(queue_summary.last_append_time.is_none());
+    assert!(queue_summary.last_truncate_time.is_none());
+    assert_eq!(
+        summary.queues["volatile"].durability_class,
+        crate::DurabilityClass::Volatile
+    );
+
+    multi_record_log
+        .append_records("queue", None, [&b"hello"[..], &b"world!"[..]].into_iter())
+        .unwrap();
+    multi_record_log.truncate("queue", ..=0).unwrap();
+    let summary = multi_record_log.summary();
+    let queue_summary = &summary.queues["queue"];
+    assert_eq!(queue_summary.start, 1);
+    assert_eq!(queue_summary.end, Some(1));
+    assert_eq!(queue_summary.file_number, Some(0));
+    assert_eq!(queue_summary.num_records, 1);
+    assert_eq!(queue_summary.payload_bytes, 6);
+    assert!(queue_summary.memory_capacity_bytes >= 6);
+    assert_eq!(queue_summary.num_files, 1);
+    assert!(queue_summary.last_append_time.is_some());
+    assert!(queue_summary.last_truncate_time.is_some());
+    assert_eq!(summary.global.num_files, 1);
+    assert_eq!(summary.global.current_file_number, 0);
+    assert!(matches!(
+        summary.global.persist_policy,
+        crate::PersistPolicy::Always(crate::PersistAction::Flush)
+    ));
+
+    let json = serde_json::to_string(&summary).unwrap();
+    let deserialized: crate::QueuesSummary = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized.queues, summary.queues);
+    assert_eq!(deserialized.global.num_files, 1);
+
+    // Summaries serialized before the detailed fields were added.
+    let old_json = r#"{"queues":{"queue":{"start":1,"end":1,"file_number":0}}}"#;
+    let deserialized: crate::QueuesSummary = serde_json::from_str(old_json).unwrap();
+    let queue_summary = &deserialized.queues["queue"];
+    assert_eq!(queue_summary.start, 1);
+    assert_eq!(queue_summary.end, Some(1));
+    assert_eq!(queue_summary.num_records, 0);
+    assert!(queue_summary.last_append_time.is_none());
+    assert_eq!(deserialized.global.num_files, 0);
+
+    drop(multi_record_log);
+    // Replaying the wal does not count as an append.
+    let multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+    let summary = multi_record_log.summary();
+    assert_eq!(summary.queues["queue"].num_records, 1);
+    assert!(summary.queues["queue"].last_append_time.is_none());
+    assert!(!summary.queues.contains_key("volatile"));
+}
+
+#[test]
+fn test_range_owned() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+    multi_record_log.create_queue("queue").unwrap();
+    multi_record_log
+        .append_records("queue", None, [&b"hello"[..], &b"world"[..]].into_iter())
+        .unwrap();
+    let records: Vec<OwnedRecord> = multi_record_log
+        .range_owned("queue", 1..)
+        .unwrap()
+        .collect();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].position, 1);
+    // The records outlive appends, truncations and the deletion of the queue.
+    multi_record_log
+        .append_record("queue", None, &b"again"[..])
+        .unwrap();
+    multi_record_log.truncate("queue", ..=2).unwrap();
+    multi_record_log.delete_queue("queue").unwrap();
+    assert_eq!(&records[0].payload[..], b"world");
+    assert!(matches!(
+        multi_record_log.range_owned("queue", ..),
+        Err(crate::error::MissingQueue(_))
+    ));
+}
+
+#[test]
+fn test_log_reader() {
+    fn assert_send_sync<T: Send + Sync + Clone>() {}
+    assert_send_sync::<crate::LogReader>();
+
+    let tempdir = tempfile::tempdir().unwrap();
+    {
+        let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        multi_record_log.create_queue("queue").unwrap();
+        multi_record_log
+            .append_record("queue", None, &b"replayed"[..])
+            .unwrap();
+    }
+    let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+    let reader = multi_record_log.reader();
+    assert_eq!(reader.last_position("queue").unwrap(), Some(0));
+    assert!(matches!(
+        reader.last_position("other-queue"),
+        Err(crate::error::MissingQueue(_))
+    ));
+
+    let writer_thread = std::thread::spawn(move || {
+        for i in 1..100u64 {
+            multi_record_log
+                .append_record("queue", None, &i.to_le_bytes()[..])
+                .unwrap();
+        }
+        multi_record_log
+    });
+    // The reader sees the appends while they happen, in order and without gaps.
+    let mut next_position = 1;
+    while next_position < 100 {
+        for record in reader.range("queue", next_position..).unwrap() {
+            assert_eq!(record.position, next_position);
+            assert_eq!(&record.payload[..], &next_position.to_le_bytes()[..]);
+            next_position += 1;
+        }
+    }
+    let mut multi_record_log = writer_thread.join().unwrap();
+
+    multi_record_log.create_queue("other-queue").unwrap();
+    multi_record_log.truncate("queue", ..=49).unwrap();
+    let records = reader.range("queue", 98..).unwrap();
+    assert_eq!(
+        records
+            .iter()
+            .map(|record| record.position)
+            .collect::<Vec<_>>(),
+        [98, 99]
+    );
+    assert_eq!(reader.range("queue", ..).unwrap().len(), 50);
+    assert_eq!(reader.range("queue", ..=50).unwrap().len(), 1);
+    assert_eq!(reader.summary().queues, multi_record_log.summary().queues);
+    assert_eq!(
+        reader.summary().global.num_files,
+        multi_record_log.summary().global.num_files
+    );
+
+    // Truncating to a future position empties the queue.
+    multi_record_log.truncate("queue", ..=200).unwrap();
+    assert!(reader.range("queue", ..).unwrap().is_empty());
+    assert_eq!(reader.last_position("queue").unwrap(), Some(200));
+
+    multi_record_log.delete_queue("queue").unwrap();
+    assert!(matches!(
+        reader.range("queue", ..),
+        Err(crate::error::MissingQueue(_))
+    ));
+    assert_eq!(
+        reader.summary().queues.keys().collect::<Vec<_>>(),
+        ["other-queue"]
+    );
+    // Further readers share the same state.
+    assert_eq!(
+        multi_record_log.reader().summary().queues,
+        reader.summary().queues
+    );
+}
+
+#[test]
+fn test_log_reader_global_summary() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+    let reader = multi_record_log.reader();
+    multi_record_log.create_queue("queue").unwrap();
+    append_until_rolled(&mut multi_record_log, "queue");
+    let global = multi_record_log.summary().global;
+    assert!(global.num_files > 1);
+    assert_eq!(reader.summary().global.num_files, global.num_files);
+    assert_eq!(
+        reader.summary().global.current_file_number,
+        global.current_file_number
+    );
+    // GC removes the files of the truncated records.
+    multi_record_log.truncate("queue", ..=9).unwrap();
+    let global = multi_record_log.summary().global;
+    assert_eq!(global.num_files, 1);
+    assert_eq!(reader.summary().global.num_files, 1);
+}
+
+#[test]
+fn test_range_with_limits() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+    multi_record_log.create_queue("queue").unwrap();
+    multi_record_log
+        .append_records(
+            "queue",
+            None,
+            [&b"aa"[..], &b"bb"[..], &b"cccccc"[..], &b"dd"[..]].into_iter(),
+        )
+        .unwrap();
+    let positions = |page: &crate::RecordPage| {
+        page.records
+            .iter()
+            .map(|record| record.position)
+            .collect::<Vec<_>>()
+    };
+
+    let page = multi_record_log
+        .range_with_limits("queue", 0, 10, 5)
+        .unwrap();
+    assert_eq!(positions(&page), [0, 1]);
+    assert_eq!(page.next_position, 2);
+    assert!(!page.end_reached);
+    // A record larger than the byte limit is returned alone.
+    let page = multi_record_log
+        .range_with_limits("queue", 2, 10, 5)
+        .unwrap();
+    assert_eq!(positions(&page), [2]);
+    assert_eq!(&page.records[0].payload[..], b"cccccc");
+    let page = multi_record_log
+        .range_with_limits("queue", 3, 10, 5)
+        .unwrap();
+    assert_eq!(positions(&page), [3]);
+    assert_eq!(page.next_position, 4);
+    assert!(page.end_reached);
+
+    let page = multi_record_log
+        .range_with_limits("queue", 0, 3, usize::MAX)
+        .unwrap();
+    assert_eq!(positions(&page), [0, 1, 2]);
+    assert!(!page.end_reached);
+    let page = multi_record_log
+        .range_with_limits("queue", 0, 0, usize::MAX)
+        .unwrap();
+    assert!(page.records.is_empty());
+    assert_eq!(page.next_position, 0);
+    assert!(!page.end_reached);
+
+    // Reading from a truncated position starts at the first record of the queue.
+    multi_record_log.truncate("queue", ..=1).unwrap();
+    let page = multi_record_log
+        .range_with_limits("queue", 0, 1, usize::MAX)
+        .unwrap();
+    assert_eq!(positions(&page), [2]);
+    assert_eq!(page.next_position, 3);
+    let page = multi_record_log
+        .range_with_limits("queue", 4, 10, 5)
+        .unwrap();
+    assert!(page.records.is_empty());
+    assert_eq!(page.next_position, 4);
+    assert!(page.end_reached);
+
+    let reader = multi_record_log.reader();
+    // Reading from a truncated position of an empty queue skips to its next position.
+    multi_record_log.truncate("queue", ..=3).unwrap();
+    let page = multi_record_log
+        .range_with_limits("queue", 0, 10, 10)
+        .unwrap();
+    assert!(page.records.is_empty());
+    assert_eq!(page.next_position, 4);
+    assert!(page.end_reached);
+    assert_eq!(reader.range_with_limits("queue", 0, 10, 10).unwrap(), page);
+    multi_record_log
+        .append_records("queue", None, [&b"aa"[..], &b"bb"[..]].into_iter())
+        .unwrap();
+    assert_eq!(
+        reader.range_with_limits("queue", 0, 10, 5).unwrap(),
+        multi_record_log
+            .range_with_limits("queue", 0, 10, 5)
+            .unwrap()
+    );
+    assert!(matches!(
+        multi_record_log.range_with_limits("other-queue", 0, 10, 5),
+        Err(crate::error::MissingQueue(_))
+    ));
+}
+
+#[test]
+fn test_open_without_writes_leaves_wal_untouched() {
+    let tempdir = tempfile::tempdir().unwrap();
+    {
+        let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        multi_record_log.create_queue("queue").unwrap();
+        multi_record_log
+            .append_record("queue", None, &b"1"[..])
+            .unwrap();
+    }
+    let read_dir = |path: &std::path::Path| {
+        let mut files: Vec<(std::ffi::OsString, Vec<u8>)> = std::fs::read_dir(path)
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                (entry.file_name(), std::fs::read(entry.path()).unwrap())
+            })
+            .collect();
+        files.sort();
+        files
+    };
+    let files_before = read_dir(tempdir.path());
+    {
+        // Nothing is written by this session.
+        let multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        assert_eq!(
+            &read_all_records(&multi_record_log, "queue")[..],
+            &[b"1".as_slice()]
+        );
+    }
+    assert_eq!(read_dir(tempdir.path()), files_before);
+    {
+        let mut multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+        multi_record_log
+            .append_record("queue", None, &b"2"[..])
+            .unwrap();
+    }
+    let multi_record_log = MultiRecordLog::open(tempdir.path()).unwrap();
+    assert_eq!(
+        &read_all_records(&multi_record_log, "queue")[..],
+        &[b"1".as_slice(), b"2".as_slice()]
+    );
+    let append_epochs: Vec<u32> = crate::inspect::WalScanner::open(tempdir.path())
+        .unwrap()
+        .filter_map(|entry_res| match entry_res.unwrap() {
+            crate::inspect::WalEntry::Record(record_info)
+                if matches!(
+                    record_info.record,
+                    crate::inspect::WalRecord::AppendRecords { .. }
+                ) =>
+            {
+                record_info.record_id.map(|(epoch, _)| epoch)
+            }
+            _ => None,
+        })
+        .collect();
+    // The session that wrote nothing did not use an epoch.
+    assert_eq!(append_epochs, [1, 2]);
+}